@@ -92,6 +92,10 @@ impl Generate<Art<i32>> for ExampleCleanDirty {
     cell(name_of_str("a"), 2)
   }
 }
+/// The `i`th edit of `ExampleCleanDirty` sets the input to this value.
+fn clean_dirty_value(i:usize) -> i32 {
+  match i { 0 => -2, 1 => 3, _ => 2 }
+}
 /// This editor helps in an example.  It creates an input cell holding
 /// the integer 2, and then edits this cell to hold -2.  Then it edits
 /// the cell to hold 3, then 2 again, and then loops (2, -2, 3, 2, ...).
@@ -101,17 +105,24 @@ impl Edit<Art<i32>, usize> for ExampleCleanDirty {
   }
   fn edit<R:Rng>(_inp:Art<i32>, i:usize,
                  _rng:&mut R, _params:&GenerateParams) -> (Art<i32>, usize) {
-    if i == 0 {
-      let inp = cell(name_of_str("a"), -2);
-      (inp, 1)
-    } 
-    else if i == 1 {
-      let inp = cell(name_of_str("a"), 3);
-      (inp, 2)
-    }
-    else {
-      let inp = cell(name_of_str("a"), 2);
-      (inp, 0)
+    let inp = cell(name_of_str("a"), clean_dirty_value(i));
+    (inp, (i + 1) % 3)
+  }
+  /// Describes each edit as `set a <value>`.
+  fn edit_recorded<R:Rng>(inp:Art<i32>, i:usize,
+                          rng:&mut R, params:&GenerateParams) -> (Art<i32>, usize, Option<String>) {
+    let (inp, j) = Self::edit(inp, i, rng, params);
+    (inp, j, Some(format!("set a {}", clean_dirty_value(i))))
+  }
+  fn replay_edit(_inp:Art<i32>, i:usize,
+                 edit:&str, _params:&GenerateParams) -> (Art<i32>, usize) {
+    let x = match edit.trim().split(' ').collect::<Vec<_>>()[..] {
+      ["set", "a", x] => x.parse::<i32>().ok(),
+      _ => None,
+    };
+    match x {
+      Some(x) => (cell(name_of_str("a"), x), (i + 1) % 3),
+      None => panic!("ExampleCleanDirty: cannot replay the edit `{}`; expected `set a <value>`", edit),
     }
   }
 }
@@ -149,6 +160,35 @@ impl Compute<Art<i32>, Art<i32>> for ExampleCleanDirty {
 
 
 
+/// Names the articulation placed atop list `l` by an editor that is
+/// at position `i`.  Under `NominalStrategy::Regular`, this name is
+/// the position `i`.  Under `NominalStrategy::ByContent`, this name is
+/// the hash of the elements of `l` that precede its first name,
+/// together with that name (which, in turn, identifies the rest of
+/// the list by its content).
+pub fn name_of_list_pos(params:&GenerateParams, i:usize, l:&List<usize>) -> Name {
+  match params.nominal_strategy {
+    NominalStrategy::Regular   => name_of_usize(i),
+    NominalStrategy::ByContent => {
+      let mut elms = vec![];
+      let nm = list_content_prefix(l, &mut elms);
+      name_of_content(&(elms, nm))
+    }
+  }
+}
+
+/// Collects the elements of `l` that precede its first name, and
+/// returns that name, if any.
+fn list_content_prefix(l:&List<usize>, elms:&mut Vec<usize>) -> Option<Name> {
+  match *l {
+    List::Nil => None,
+    List::Name(ref nm, _) => Some(nm.clone()),
+    List::Cons(ref x, ref tl) => { elms.push(*x); list_content_prefix(tl, elms) },
+    List::Art(ref a) => list_content_prefix(&force(a), elms),
+    List::Tree(_, _, ref tl) => list_content_prefix(tl, elms),
+  }
+}
+
 /// This list editor mimics the editor in the example from
 /// _Incremental Computation with Names_ (2015), Section 2 (Figs 1 and
 /// 2).
@@ -168,31 +208,31 @@ impl Compute<Art<i32>, Art<i32>> for ExampleCleanDirty {
 #[derive(Clone,Debug)]
 pub struct EditorOopsla2015Sec2 { } 
 impl Generate<List<usize>> for EditorOopsla2015Sec2 {
-  fn generate<R:Rng> (_rng:&mut R, _params:&GenerateParams) -> List<usize> {
+  fn generate<R:Rng> (_rng:&mut R, params:&GenerateParams) -> List<usize> {
     let l = list_nil();
     
-    let l = list_art( cell( name_of_str("d"), l) );
-    let l = list_name( name_of_str("delta"), l );
+    let l = list_art( cell( name_of_cell(params, "d", 3), l) );
+    let l = list_name( name_of_elm(params, "delta", 3), l );
     let l = list_cons(3, l);
 
-    let l = list_art( cell( name_of_str("b"), l) );
-    let l = list_name( name_of_str("beta"), l );
+    let l = list_art( cell( name_of_cell(params, "b", 1), l) );
+    let l = list_name( name_of_elm(params, "beta", 1), l );
     let l = list_cons(1, l);
 
-    let l = list_art( cell( name_of_str("a"), l) );
-    let l = list_name( name_of_str("alpha"), l );
+    let l = list_art( cell( name_of_cell(params, "a", 0), l) );
+    let l = list_name( name_of_elm(params, "alpha", 0), l );
     let l = list_cons(0, l);
 
-    let l = list_art( cell( name_of_str("z"), l) );
-    let l = list_name( name_of_str("zeta"), l );
+    let l = list_art( cell( name_of_cell(params, "z", 99), l) );
+    let l = list_name( name_of_elm(params, "zeta", 99), l );
     let l = list_cons(99, l);
 
-    let l = list_art( cell( name_of_str("y"), l) );
-    let l = list_name( name_of_str("yellow"), l );
+    let l = list_art( cell( name_of_cell(params, "y", 98), l) );
+    let l = list_name( name_of_elm(params, "yellow", 98), l );
     let l = list_cons(98, l);
 
-    let l = list_art( cell( name_of_str("x"), l) );
-    let l = list_name( name_of_str("xray"), l );
+    let l = list_art( cell( name_of_cell(params, "x", 97), l) );
+    let l = list_name( name_of_elm(params, "xray", 97), l );
     let l = list_cons(97, l);
     l
   }
@@ -202,7 +242,7 @@ impl Edit<List<usize>,usize> for EditorOopsla2015Sec2 {
     return 0
   }
   fn edit<R:Rng>(list:List<usize>, i:usize,
-                 _rng:&mut R, _params:&GenerateParams) -> (List<usize>, usize) {
+                 _rng:&mut R, params:&GenerateParams) -> (List<usize>, usize) {
     if i == 0 {
       let x = match list      { List::Cons(_, box List::Name(_, box List::Art(ref x))) => x.clone(), _ => unreachable!() };
       let y = match force(&x) { List::Cons(_, box List::Name(_, box List::Art(ref y))) => y.clone(), _ => unreachable!() };
@@ -213,36 +253,36 @@ impl Edit<List<usize>,usize> for EditorOopsla2015Sec2 {
 
       // Create the new Cons cell, new name and new ref cell, which
       // points at the tail of the existing list, `l`, above.
-      let l = list_art(cell(name_of_str("c"), l));
-      let l = list_name(name_of_str("gamma"), l);
+      let l = list_art(cell(name_of_cell(params, "c", 2), l));
+      let l = list_name(name_of_elm(params, "gamma", 2), l);
       let l = list_cons(2, l);
       
       // The following ways of mutating cell b are equivalent for the
       // DCG, though only the first way is defined for the Naive
       // engine:
       if true {
-        let l = list_art(cell( name_of_str("b"), l));
+        let l = list_art(cell( name_of_cell(params, "b", 1), l));
         
         // The rest of this is copied from the Generate impl.  We have
         // to do these steps to keep the Naive version (which does not
         // have a store) in sync with the DCG's input (which need not do
         // these steps):
-        let l = list_name( name_of_str("beta"), l );
+        let l = list_name( name_of_elm(params, "beta", 1), l );
         let l = list_cons(1, l);      
-        let l = list_art( cell( name_of_str("a"), l) );
-        let l = list_name( name_of_str("alpha"), l );
+        let l = list_art( cell( name_of_cell(params, "a", 0), l) );
+        let l = list_name( name_of_elm(params, "alpha", 0), l );
         let l = list_cons(0, l);
         
-        let l = list_art( cell( name_of_str("z"), l) );
-        let l = list_name( name_of_str("zeta"), l );
+        let l = list_art( cell( name_of_cell(params, "z", 99), l) );
+        let l = list_name( name_of_elm(params, "zeta", 99), l );
         let l = list_cons(99, l);
         
-        let l = list_art( cell( name_of_str("y"), l) );
-        let l = list_name( name_of_str("yellow"), l );
+        let l = list_art( cell( name_of_cell(params, "y", 98), l) );
+        let l = list_name( name_of_elm(params, "yellow", 98), l );
         let l = list_cons(98, l);
 
-        let l = list_art( cell( name_of_str("x"), l) );
-        let l = list_name( name_of_str("xray"), l );
+        let l = list_art( cell( name_of_cell(params, "x", 97), l) );
+        let l = list_name( name_of_elm(params, "xray", 97), l );
         let l = list_cons(97, l);
 
         return (l, 1)
@@ -348,11 +388,11 @@ pub mod oopsla2015_sec2 {
   }
 
   impl Generate<List<usize>> for Editor {
-    fn generate<R:Rng> (_rng:&mut R, _params:&GenerateParams) -> List<usize> {
+    fn generate<R:Rng> (_rng:&mut R, params:&GenerateParams) -> List<usize> {
       let l = List::Nil;
-      let l = List::Cons(3, name_of_str("delta"), cell(name_of_str("d"), l));
-      let l = List::Cons(1, name_of_str("beta"), cell(name_of_str("b"), l));
-      let l = List::Cons(0, name_of_str("alpha"), cell(name_of_str("a"), l));
+      let l = List::Cons(3, name_of_elm(params, "delta", 3), cell(name_of_cell(params, "d", 3), l));
+      let l = List::Cons(1, name_of_elm(params, "beta", 1), cell(name_of_cell(params, "b", 1), l));
+      let l = List::Cons(0, name_of_elm(params, "alpha", 0), cell(name_of_cell(params, "a", 0), l));
       l
     }
   }
@@ -361,7 +401,7 @@ pub mod oopsla2015_sec2 {
       return 0
     }
     fn edit<R:Rng>(list:List<usize>, i:usize,
-                   _rng:&mut R, _params:&GenerateParams) -> (List<usize>, usize) {
+                   _rng:&mut R, params:&GenerateParams) -> (List<usize>, usize) {
       if i == 0 {
         let a = match list.clone() { List::Cons(_, _, a) => a.clone(), _ => unreachable!() };
         let b = match force(&a)    { List::Cons(_, _, b) => b.clone(), _ => unreachable!() };
@@ -369,21 +409,21 @@ pub mod oopsla2015_sec2 {
         
         // Create the new Cons cell, new name and new ref cell, which
         // points at the tail of the existing list, `b`, above.
-        let l = List::Cons(2, name_of_str("gamma"), cell(name_of_str("c"), l));
+        let l = List::Cons(2, name_of_elm(params, "gamma", 2), cell(name_of_cell(params, "c", 2), l));
         
         // The following ways of mutating cell b are equivalent for the
         // DCG, though only the first way is defined for the Naive
         // engine:
         if true {
           // Mutate the cell called 'b' to hold this new list:
-          let l = cell(name_of_str("b"), l);
+          let l = cell(name_of_cell(params, "b", 1), l);
           
           // The rest of this is copied from the Generate impl.  We have
           // to do these steps to keep the Naive version (which does not
           // have a store) in sync with the DCG's input (which need not do
           // these steps):        
-          let l = List::Cons(1, name_of_str("beta"), l);
-          let l = List::Cons(0, name_of_str("alpha"), cell(name_of_str("a"), l));
+          let l = List::Cons(1, name_of_elm(params, "beta", 1), l);
+          let l = List::Cons(0, name_of_elm(params, "alpha", 0), cell(name_of_cell(params, "a", 0), l));
           
           return (l, 1)
         } else {
@@ -485,11 +525,11 @@ pub mod hammer_s17_hw0 {
   pub struct Editor { } 
 
   impl Generate<List<usize>> for Editor {
-    fn generate<R:Rng> (_rng:&mut R, _params:&GenerateParams) -> List<usize> {
+    fn generate<R:Rng> (_rng:&mut R, params:&GenerateParams) -> List<usize> {
       let l = List::Nil;
-      let l = List::Cons(3, name_of_str("delta"), cell(name_of_str("d"), l));
-      let l = List::Cons(1, name_of_str("beta"), cell(name_of_str("b"), l));
-      let l = List::Cons(0, name_of_str("alpha"), cell(name_of_str("a"), l));
+      let l = List::Cons(3, name_of_elm(params, "delta", 3), cell(name_of_cell(params, "d", 3), l));
+      let l = List::Cons(1, name_of_elm(params, "beta", 1), cell(name_of_cell(params, "b", 1), l));
+      let l = List::Cons(0, name_of_elm(params, "alpha", 0), cell(name_of_cell(params, "a", 0), l));
       l
     }
   }
@@ -498,7 +538,7 @@ pub mod hammer_s17_hw0 {
       return 0
     }
     fn edit<R:Rng>(list:List<usize>, i:usize,
-                   _rng:&mut R, _params:&GenerateParams) -> (List<usize>, usize) {
+                   _rng:&mut R, params:&GenerateParams) -> (List<usize>, usize) {
       if i == 0 {
         let a = match list.clone() { List::Cons(_, _, a) => a.clone(), _ => unreachable!() };
         let b = match force(&a)    { List::Cons(_, _, b) => b.clone(), _ => unreachable!() };
@@ -506,21 +546,21 @@ pub mod hammer_s17_hw0 {
         
         // Create the new Cons cell, new name and new ref cell, which
         // points at the tail of the existing list, `b`, above.
-        let l = List::Cons(2, name_of_str("gamma"), cell(name_of_str("c"), l));
+        let l = List::Cons(2, name_of_elm(params, "gamma", 2), cell(name_of_cell(params, "c", 2), l));
         
         // The following ways of mutating cell b are equivalent for the
         // DCG, though only the first way is defined for the Naive
         // engine:
         if true {
           // Mutate the cell called 'b' to hold this new list:
-          let l = cell(name_of_str("b"), l);
+          let l = cell(name_of_cell(params, "b", 1), l);
           
           // The rest of this is copied from the Generate impl.  We have
           // to do these steps to keep the Naive version (which does not
           // have a store) in sync with the DCG's input (which need not do
           // these steps):        
-          let l = List::Cons(1, name_of_str("beta"), l);
-          let l = List::Cons(0, name_of_str("alpha"), cell(name_of_str("a"), l));
+          let l = List::Cons(1, name_of_elm(params, "beta", 1), l);
+          let l = List::Cons(0, name_of_elm(params, "alpha", 0), cell(name_of_cell(params, "a", 0), l));
           
           return (l, 1)
         } else {
@@ -731,8 +771,9 @@ impl<S> Generate<List<usize>> for UniformPrepend<List<usize>,S> {
     let mut l : List<usize> = list_nil();
     for i in 0..params.size {
       if i % params.gauge == 0 {
-        l = list_art(cell(name_of_usize(i), l));
-        l = list_name(name_of_usize(i), l);
+        let nm = name_of_list_pos(params, i, &l);
+        l = list_art(cell(nm.clone(), l));
+        l = list_name(nm, l);
       } else { } ;
       let elm : usize = rng.gen() ;
       let elm = elm % ( params.size * 100 ) ;
@@ -753,21 +794,46 @@ impl Edit<List<usize>, usize> for UniformPrepend<List<usize>,usize> {
   fn edit<R:Rng>(l_preedit:List<usize>, 
                  next_name:usize,
                  rng:&mut R, params:&GenerateParams) -> (List<usize>, usize) {
-    let mut l = l_preedit ;
-    let i = next_name ;
-    if i % params.gauge == 0 {
-      l = list_art(cell(name_of_usize(i), l));
-      l = list_name(name_of_usize(i), l);      
-    } else { } ;
     let elm : usize = rng.gen() ;
     let elm = elm % ( params.size * 100 ) ;
-    l = list_cons(elm, l);
-    if i % params.gauge == 0 {
-      //l = list_art(cell(name_of_usize(i), l));
-      //l = list_name(name_of_usize(i), l);      
-    } else { } ;
-    (l, i + 1)
+    list_prepend(l_preedit, next_name, elm, params)
   }
+  /// Describes each edit as `prepend <elm>`.
+  fn edit_recorded<R:Rng>(l_preedit:List<usize>, next_name:usize,
+                          rng:&mut R, params:&GenerateParams) -> (List<usize>, usize, Option<String>) {
+    let elm : usize = rng.gen() ;
+    let elm = elm % ( params.size * 100 ) ;
+    let (l, i) = list_prepend(l_preedit, next_name, elm, params);
+    (l, i, Some(format!("prepend {}", elm)))
+  }
+  fn replay_edit(l_preedit:List<usize>, next_name:usize,
+                 edit:&str, params:&GenerateParams) -> (List<usize>, usize) {
+    let elm = match edit.trim().split(' ').collect::<Vec<_>>()[..] {
+      ["prepend", elm] => elm.parse::<usize>().ok(),
+      _ => None,
+    };
+    match elm {
+      Some(elm) => list_prepend(l_preedit, next_name, elm, params),
+      None => panic!("UniformPrepend: cannot replay the edit `{}`; expected `prepend <elm>`", edit),
+    }
+  }
+}
+
+/// Prepends `elm` to the list `l`, whose next name is `i`; the edit of
+/// `UniformPrepend`, after choosing `elm`.
+fn list_prepend(l:List<usize>, i:usize, elm:usize, params:&GenerateParams) -> (List<usize>, usize) {
+  let mut l = l ;
+  if i % params.gauge == 0 {
+    let nm = name_of_list_pos(params, i, &l);
+    l = list_art(cell(nm.clone(), l));
+    l = list_name(nm, l);
+  } else { } ;
+  l = list_cons(elm, l);
+  if i % params.gauge == 0 {
+    //l = list_art(cell(name_of_usize(i), l));
+    //l = list_name(name_of_usize(i), l);      
+  } else { } ;
+  (l, i + 1)
 }
 
 
@@ -1042,24 +1108,6 @@ impl Compute<List<Pt2D>,List<Pt2D>> for Quickhull {
 //   }
 // }
 
-#[macro_export]
-macro_rules! labdef {
-  ( $name:expr, $url:expr, $inp:ty, $editst:ty, $out:ty, $dist:ty, $comp:ty ) => {{ 
-    Box::new( 
-      LabDef
-        ::<$inp,$editst,$out,$dist,$comp>
-      { 
-        identity:$name,
-        url:$url,
-        input:PhantomData,
-        editst:PhantomData,
-        output:PhantomData,
-        editor:PhantomData,
-        archivist:PhantomData
-      }) 
-  }}
-}
-
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - 
 /// `all_labs`: This is the master list of all labs in the current
 /// Adapton Lab catalog (which consists of this module, but which
@@ -1083,6 +1131,14 @@ macro_rules! labdef {
 /// url to display in generated output.  The URL should link to the
 /// rustdoc for this module, which in turn provides other related
 /// documentation about Adapton Lab and Adapton.
+///
+/// 4. Finally, describe the lab (See `LabInfo`): a sentence on what
+/// it computes, its tags (e.g., `list` and `lazy`), for selecting it
+/// with `--tag`, reusing the tags of similar labs, whether its
+/// Archivist is lazy, and its own defaults, if any (e.g., for an
+/// Editor that ignores the input size), and its own viz and bench
+/// presets, if any (e.g., a smaller input for `bench`, for an
+/// Archivist that recurs over the whole list).
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - 
 pub fn all_labs() -> Vec<Box<Lab>> {
   return vec![
@@ -1091,15 +1147,22 @@ pub fn all_labs() -> Vec<Box<Lab>> {
             Art<i32>, usize,
             Art<i32>,
             ExampleCleanDirty,
-            ExampleCleanDirty)
+            ExampleCleanDirty;
+            tags: vec!["example"];
+            description: "Squares an input cell, and caps the square at 100, to show how change propagation dirties and cleans the DCG.";
+            defaults: ParamsOverride{ size: Some(1), ..ParamsOverride::default() };
+            bench: ParamsOverride{ change_batch_loopc: Some(100), ..ParamsOverride::default() })
       ,
 
     labdef!(name_of_str("eg-oopsla2015-sec2"),
-            Some(String::from("")),
+            None,
             oopsla2015_sec2::List<usize>, usize,
             oopsla2015_sec2::List<usize>,
             oopsla2015_sec2::Editor,
-            oopsla2015_sec2::Archivist)
+            oopsla2015_sec2::Archivist;
+            tags: vec!["example", "list"];
+            description: "Maps over a three-element list, and then inserts an element, as in Section 2 of Incremental Computation with Names (OOPSLA 2015).";
+            defaults: ParamsOverride{ size: Some(3), change_batch_loopc: Some(1), ..ParamsOverride::default() })
       ,
 
     labdef!(name_of_str("eg-oopsla2015-sec2-rev1"),
@@ -1107,14 +1170,20 @@ pub fn all_labs() -> Vec<Box<Lab>> {
             List<usize>, usize,
             List<usize>,
             EditorOopsla2015Sec2,
-            EagerMap)
+            EagerMap;
+            tags: vec!["example", "list", "eager", "map"];
+            description: "Like eg-oopsla2015-sec2, with the lists of the Adapton collections library, and their eager map.";
+            defaults: ParamsOverride{ size: Some(3), change_batch_loopc: Some(1), ..ParamsOverride::default() })
       ,
     labdef!(name_of_str("eg-oopsla2015-sec2-rev2"),
             Some(String::from("http://adapton.org/rustdoc/adapton_lab/catalog/struct.EditorOopsla2015Sec2.html")),
             List<usize>, usize,
             List<usize>,
             EditorOopsla2015Sec2,
-            EagerMap2)
+            EagerMap2;
+            tags: vec!["example", "list", "eager", "map"];
+            description: "Like eg-oopsla2015-sec2-rev1, with another eager map of the Adapton collections library.";
+            defaults: ParamsOverride{ size: Some(3), change_batch_loopc: Some(1), ..ParamsOverride::default() })
       ,
 
 
@@ -1123,14 +1192,22 @@ pub fn all_labs() -> Vec<Box<Lab>> {
             List<usize>, usize,
             List<usize>,
             UniformPrepend<_,_>,
-            LazyMap)
+            LazyMap;
+            tags: vec!["list", "lazy", "map"];
+            description: "Squares each element of a list, lazily, demanding a prefix of the output.";
+            lazy: true;
+            bench: ParamsOverride{ size: Some(20000), ..ParamsOverride::default() })
       ,
     labdef!(name_of_str("list-lazy-filter"),
             Some(String::from("http://adapton.org/rustdoc/adapton_lab/catalog/struct.LazyFilter.html")),
             List<usize>, usize,
             List<usize>,
             UniformPrepend<_,_>,
-            LazyFilter)
+            LazyFilter;
+            tags: vec!["list", "lazy", "filter"];
+            description: "Keeps the multiples of three of a list, lazily, demanding a prefix of the output.";
+            lazy: true;
+            bench: ParamsOverride{ size: Some(20000), ..ParamsOverride::default() })
       ,
     
     
@@ -1139,21 +1216,27 @@ pub fn all_labs() -> Vec<Box<Lab>> {
             List<usize>, usize,
             Tree<usize>,
             UniformPrepend<_,_>,
-            ListTree)
+            ListTree;
+            tags: vec!["list", "tree", "eager"];
+            description: "Converts a list into a balanced tree.")
       ,
     labdef!(name_of_str("list-tree-max"),
             Some(String::from("http://adapton.org/rustdoc/adapton_lab/catalog/struct.ListTreeMax.html")),
             List<usize>, usize,
             usize,
             UniformPrepend<_,_>,
-            ListTreeMax)
+            ListTreeMax;
+            tags: vec!["list", "tree", "eager", "fold"];
+            description: "Converts a list into a balanced tree, and folds the tree into the maximum element.")
       ,
     labdef!(name_of_str("list-tree-sum"),
             Some(String::from("http://adapton.org/rustdoc/adapton_lab/catalog/struct.ListTreeSum.html")),
             List<usize>, usize,
             usize,
             UniformPrepend<_,_>,
-            ListTreeSum)
+            ListTreeSum;
+            tags: vec!["list", "tree", "eager", "fold"];
+            description: "Converts a list into a balanced tree, and folds the tree into the sum of the elements.")
       ,
     
     // labdef!(name_of_str("list-eager-mergesort3"),
@@ -1168,7 +1251,12 @@ pub fn all_labs() -> Vec<Box<Lab>> {
             List<usize>, usize,
             List<usize>,
             UniformPrepend<_,_>,
-            LazyMergesort3)
+            LazyMergesort3;
+            tags: vec!["list", "tree", "lazy", "sort"];
+            description: "Sorts a list with a lazy mergesort over a balanced tree of the list, demanding a prefix of the output.";
+            lazy: true;
+            viz: ParamsOverride{ size: Some(16), demand: Some(2), ..ParamsOverride::default() };
+            bench: ParamsOverride{ size: Some(100000), demand: Some(1), ..ParamsOverride::default() })
       ,
     
     // labdef!(name_of_str("list-eager-mergesort2"),
@@ -1183,7 +1271,12 @@ pub fn all_labs() -> Vec<Box<Lab>> {
             List<usize>, usize,
             List<usize>,
             UniformPrepend<_,_>,
-            LazyMergesort2)
+            LazyMergesort2;
+            tags: vec!["list", "tree", "lazy", "sort"];
+            description: "Sorts a list with a lazy mergesort over a balanced tree of the list, demanding a prefix of the output.";
+            lazy: true;
+            viz: ParamsOverride{ size: Some(16), demand: Some(2), ..ParamsOverride::default() };
+            bench: ParamsOverride{ size: Some(100000), demand: Some(1), ..ParamsOverride::default() })
       ,
     
     // labdef!(name_of_str("list-eager-mergesort1"),
@@ -1198,7 +1291,12 @@ pub fn all_labs() -> Vec<Box<Lab>> {
             List<usize>, usize,
             List<usize>,
             UniformPrepend<_,_>,
-            LazyMergesort1)
+            LazyMergesort1;
+            tags: vec!["list", "tree", "lazy", "sort"];
+            description: "Sorts a list with a lazy mergesort over a balanced tree of the list, demanding a prefix of the output.";
+            lazy: true;
+            viz: ParamsOverride{ size: Some(16), demand: Some(2), ..ParamsOverride::default() };
+            bench: ParamsOverride{ size: Some(100000), demand: Some(1), ..ParamsOverride::default() })
       ,
     
     labdef!(name_of_str("list-eager-map"),
@@ -1206,21 +1304,30 @@ pub fn all_labs() -> Vec<Box<Lab>> {
             List<usize>, usize,
             List<usize>,
             UniformPrepend<_,_>,
-            EagerMap)
+            EagerMap;
+            tags: vec!["list", "eager", "map"];
+            description: "Squares each element of a list, eagerly.";
+            bench: ParamsOverride{ size: Some(10000), ..ParamsOverride::default() })
       ,
     labdef!(name_of_str("list-eager-filter"),
             Some(String::from("http://adapton.org/rustdoc/adapton_lab/catalog/struct.EagerFilter.html")),
             List<usize>, usize,
             List<usize>,
             UniformPrepend<_,_>,
-            EagerFilter)
+            EagerFilter;
+            tags: vec!["list", "eager", "filter"];
+            description: "Keeps the multiples of three of a list, eagerly.";
+            bench: ParamsOverride{ size: Some(10000), ..ParamsOverride::default() })
       ,
     labdef!(name_of_str("list-reverse"),
             Some(String::from("http://adapton.org/rustdoc/adapton_lab/catalog/struct.ListReverse.html")),
             List<usize>, usize,
             List<usize>,
             UniformPrepend<_,_>,
-            ListReverse)
+            ListReverse;
+            tags: vec!["list", "eager"];
+            description: "Reverses a list.";
+            bench: ParamsOverride{ size: Some(5000), ..ParamsOverride::default() })
     ,
     // labdef!(name_of_str("raz-max"),
     //         Some(String::from("http://adapton.org/rustdoc/adapton_lab/catalog/struct.RazMax.html")),
@@ -1322,3 +1429,79 @@ pub fn all_labs() -> Vec<Box<Lab>> {
 
   ]
 }
+
+// The labs whose outputs we can compare for equality across engines:
+lab_test!(list_tree_max_is_consistent,
+          labdef!(name_of_str("list-tree-max"), None,
+                  List<usize>, usize, usize,
+                  UniformPrepend<_,_>, ListTreeMax));
+
+lab_test!(list_tree_sum_is_consistent,
+          labdef!(name_of_str("list-tree-sum"), None,
+                  List<usize>, usize, usize,
+                  UniformPrepend<_,_>, ListTreeSum));
+
+lab_test!(list_tree_max_is_consistent_by_content,
+          labdef!(name_of_str("list-tree-max"), None,
+                  List<usize>, usize, usize,
+                  UniformPrepend<_,_>, ListTreeMax),
+          0..10, 10, NominalStrategy::ByContent);
+
+lab_test!(list_tree_sum_is_consistent_by_content,
+          labdef!(name_of_str("list-tree-sum"), None,
+                  List<usize>, usize, usize,
+                  UniformPrepend<_,_>, ListTreeSum),
+          0..10, 10, NominalStrategy::ByContent);
+
+// Under `NominalStrategy::ByContent`, the examples name each element
+// and its ref cell by the element, and must keep these names apart
+#[test]
+fn example_names_by_content_are_distinct() {
+  let params = GenerateParams{ size:0, gauge:1, nominal_strategy:NominalStrategy::ByContent };
+  assert!(name_of_cell(&params, "b", 1) != name_of_elm(&params, "beta", 1));
+  assert!(name_of_cell(&params, "b", 1) != name_of_cell(&params, "c", 2));
+}
+
+#[test]
+fn eg_oopsla2015_sec2_runs_by_content() {
+  let lab : Box<Lab> = labdef!(name_of_str("eg-oopsla2015-sec2"), None,
+                               oopsla2015_sec2::List<usize>, usize,
+                               oopsla2015_sec2::List<usize>,
+                               oopsla2015_sec2::Editor,
+                               oopsla2015_sec2::Archivist);
+  let mut params = ::adapton_lab::labtest::consistency_params((0..10).collect(), 3);
+  params.sample_params.generate_params.nominal_strategy = NominalStrategy::ByContent;
+  let results = lab.run(&lab_params(&params, lab.info()));
+  assert_eq!(results.failures().len(), 0, "{:?}", results.failures());
+  assert_eq!(results.trajectories.iter().map(|t| t.samples.len()).sum::<usize>(), 10 * 4);
+
+  // The outputs hold articulations, which differ by engine, so we
+  // compare their elements, after each edit
+  use catalog::oopsla2015_sec2::{List,Editor,Archivist};
+  use adapton::engine::manage::{init_naive,init_dcg};
+  fn elms(l:List<usize>) -> Vec<usize> {
+    match l {
+      List::Nil => vec![],
+      List::Cons(x, _, xs) => { let mut v = vec![x]; v.extend(elms(force(&xs))); v },
+    }
+  }
+  let gp = &params.sample_params.generate_params;
+  let outputs = || -> Vec<Vec<usize>> {
+    let mut rng = rand::thread_rng();
+    let mut st  = <Editor as Edit<List<usize>,usize>>::edit_init(&mut rng, gp);
+    let mut inp = <Editor as Generate<List<usize>>>::generate(&mut rng, gp);
+    let mut outs = vec![];
+    for _ in 0..4 {
+      outs.push(elms(<Archivist as Compute<List<usize>,List<usize>>>::compute(inp.clone())));
+      let (inp2, st2) = Editor::edit(inp, st, &mut rng, gp);
+      inp = inp2; st = st2;
+    };
+    outs
+  };
+  let _ = init_naive();
+  let naive_outputs = outputs();
+  let _ = init_dcg();
+  let dcg_outputs = outputs();
+  assert_eq!(dcg_outputs, naive_outputs);
+  assert_eq!(dcg_outputs[1], vec![0, 1, 4, 9]);
+}
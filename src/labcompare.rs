@@ -0,0 +1,291 @@
+use std::fs;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::fs::File;
+use std::collections::HashMap;
+
+use labdata::{DataRow,DataVal,OutputFormat,write_data_rows,extension_of_format,string_of_data_val};
+
+/// The columns of a sample (See `data_row_of_sample`) that determine
+/// its parameter point; with the lab name, we match the points of a
+/// run to those of the baseline.
+pub const POINT_COLUMNS : [&'static str; 6] =
+  ["size", "gauge", "nominal_strategy", "demand", "change_batch_size", "change_batch_loopc"];
+
+/// The metric of the DCG's update time: the mean, over the matched
+/// updates, of the median time of each (its `dcg_compute_ns`).
+pub const MEAN_TIME_METRIC : &'static str = "mean_dcg_compute_ns";
+
+/// How much worse each metric may get before we call it a regression,
+/// as a relative change, e.g., `0.1` permits 10% more time.
+#[derive(Clone,Debug)]
+pub struct Tolerance {
+  /// For the DCG update time, which is noisy.
+  pub time:    f64,
+  /// For each effect count of the DCG update, which is deterministic
+  /// for a given input seed and edit trajectory.
+  pub effects: f64,
+}
+
+/// The change of one metric, at one lab and parameter point, from the
+/// baseline to the current run.
+#[derive(Clone,Debug)]
+pub struct MetricChange {
+  pub labname:    String,
+  /// The parameter point, as `column=value` pairs (See `POINT_COLUMNS`).
+  pub point:      String,
+  /// `mean_dcg_compute_ns`, for the DCG's update time (the mean,
+  /// over the updates, of the median time of each; See
+  /// `MEAN_TIME_METRIC`), or the name of an effect count (e.g.,
+  /// `archivist_clean_eval`), summed over the updates.
+  pub metric:     String,
+  /// The number of samples of the current run matched in the baseline.
+  pub samples:    usize,
+  pub baseline:   f64,
+  pub current:    f64,
+  pub regression: bool,
+}
+
+impl MetricChange {
+  /// The relative change from the baseline, e.g., `0.25` for 25% more;
+  /// infinite if the baseline is zero and the current value is not.
+  pub fn change(&self) -> f64 {
+    if self.baseline == self.current { 0.0 }
+    else if self.baseline == 0.0 { ::std::f64::INFINITY }
+    else { (self.current - self.baseline) / self.baseline }
+  }
+}
+
+/// The comparison of a run to a baseline (See `compare_data_rows`).
+#[derive(Clone,Debug)]
+pub struct Comparison {
+  pub changes:   Vec<MetricChange>,
+  /// The labs and points of the current run that are not in the
+  /// baseline (or that share no input seed and batch with it).
+  pub unmatched: Vec<(String, String)>,
+}
+
+impl Comparison {
+  pub fn regressions(&self) -> Vec<&MetricChange> {
+    self.changes.iter().filter(|c| c.regression).collect()
+  }
+}
+
+fn field(row:&DataRow, col:&str) -> String {
+  match row.iter().find(|&&(ref c, _)| c == col) {
+    Some(&(_, ref v)) => string_of_data_val(v),
+    None => String::new(),
+  }
+}
+
+fn number(row:&DataRow, col:&str) -> Option<f64> {
+  field(row, col).parse::<f64>().ok()
+}
+
+fn point_of_row(row:&DataRow) -> String {
+  let fields : Vec<_> = POINT_COLUMNS.iter().map(|col| format!("{}={}", col, field(row, col))).collect();
+  fields.join(" ")
+}
+
+/// Compares the samples of a run to those of a baseline, e.g., rows
+/// read back from a `samples.csv` of an earlier run (See
+/// `read_data_rows_file`).  We match the samples by lab, parameter
+/// point, input seed and batch; we compare only the updates (batches
+/// after the first), which measure change propagation.  For each lab
+/// and point, we compare the mean DCG update time (See
+/// `MEAN_TIME_METRIC`), and each effect
+/// count of the DCG updates (if both runs counted effects), and call
+/// any increase beyond the tolerance a regression.
+pub fn compare_data_rows(baseline:&Vec<DataRow>, current:&Vec<DataRow>, tolerance:&Tolerance) -> Comparison {
+  let sample_key = |row:&DataRow| (field(row, "lab"), point_of_row(row), field(row, "seed"), field(row, "batch_name"));
+  let mut baseline_samples = HashMap::new();
+  for row in baseline.iter() {
+    baseline_samples.insert(sample_key(row), row);
+  };
+  // The matched pairs of samples, for each lab and point, in the order of the current run
+  let mut points : Vec<(String, String)> = vec![];
+  let mut pairs : HashMap<(String, String), Vec<(&DataRow, &DataRow)>> = HashMap::new();
+  for row in current.iter() {
+    let key = sample_key(row);
+    let point = (key.0.clone(), key.1.clone());
+    if !pairs.contains_key(&point) {
+      points.push(point.clone());
+      pairs.insert(point.clone(), vec![]);
+    };
+    if key.3 == "0" { continue };
+    match baseline_samples.get(&key) {
+      None => (),
+      Some(b) => pairs.get_mut(&point).unwrap().push((*b, row)),
+    }
+  };
+  let mut changes = vec![];
+  let mut unmatched = vec![];
+  for point in points.into_iter() {
+    let pairs = &pairs[&point];
+    if pairs.len() == 0 { unmatched.push(point); continue };
+    let change = |metric:String, baseline:f64, current:f64, tol:f64| MetricChange{
+      labname:    point.0.clone(),
+      point:      point.1.clone(),
+      metric,
+      samples:    pairs.len(),
+      baseline,
+      current,
+      regression: current > baseline * (1.0 + tol),
+    };
+    let n = pairs.len() as f64;
+    // The sums of a column over the baseline and current samples
+    let sum = |col:&str| pairs.iter().fold((0.0, 0.0), |(b, c), &(b_row, c_row)| {
+      (b + number(b_row, col).unwrap_or(0.0), c + number(c_row, col).unwrap_or(0.0))
+    });
+    let (b_ns, c_ns) = sum("dcg_compute_ns");
+    changes.push(change(String::from(MEAN_TIME_METRIC), b_ns / n, c_ns / n, tolerance.time));
+    let counted = pairs.iter().all(|&(b, c)| field(b, "count_effects") == "true" && field(c, "count_effects") == "true");
+    if counted {
+      let cols : Vec<String> = pairs[0].1.iter().map(|&(ref col, _)| col.clone())
+        .filter(|col| col.starts_with("archivist_")).collect();
+      for col in cols.iter() {
+        let (b, c) = sum(col);
+        // Omit the effects that neither run has
+        if b == 0.0 && c == 0.0 { continue };
+        changes.push(change(col.clone(), b, c, tolerance.effects))
+      }
+    }
+  };
+  Comparison{ changes, unmatched }
+}
+
+/// Flattens a metric change into one row.
+pub fn data_row_of_metric_change(c:&MetricChange) -> DataRow {
+  let change = c.change();
+  let row : Vec<(&'static str, DataVal)> = vec![
+    ("lab",        DataVal::Str(c.labname.clone())),
+    ("point",      DataVal::Str(c.point.clone())),
+    ("metric",     DataVal::Str(c.metric.clone())),
+    ("samples",    DataVal::Nat(c.samples as u64)),
+    ("baseline",   DataVal::Num(c.baseline)),
+    ("current",    DataVal::Num(c.current)),
+    ("change",     if change.is_finite() { DataVal::Num(change) } else { DataVal::Null }),
+    ("regression", DataVal::Bool(c.regression)),
+  ];
+  row.into_iter().map(|(col, v)| (String::from(col), v)).collect()
+}
+
+/// Writes a comparison to `<out>/regressions.<ext>`, with one row per
+/// lab, point and metric (See `data_row_of_metric_change`).
+pub fn write_comparison(out:&str, format:&OutputFormat, comparison:&Comparison) {
+  let rows = comparison.changes.iter().map(data_row_of_metric_change).collect();
+  fs::create_dir_all(out).unwrap();
+  let f = File::create(format!("{}/regressions.{}", out, extension_of_format(format))).unwrap();
+  let mut writer = BufWriter::new(f);
+  write_data_rows(&mut writer, format, &rows);
+  writer.flush().unwrap();
+}
+
+/// Reports a comparison as text: each regression, each lab and point
+/// without a baseline, and a summary line.
+pub fn write_comparison_report<W:Write>(writer:&mut W, comparison:&Comparison, tolerance:&Tolerance) {
+  for c in comparison.regressions() {
+    writeln!(writer, "Regression: {}: {}: {}: {} -> {} ({:+.1}%)",
+             c.labname, c.point, c.metric, c.baseline, c.current, 100.0 * c.change()).unwrap();
+  };
+  for &(ref labname, ref point) in comparison.unmatched.iter() {
+    writeln!(writer, "Warning: no baseline for {}: {}", labname, point).unwrap();
+  };
+  writeln!(writer, "Compared {} metrics to the baseline (tolerance: {}% for time, {}% for effects): {} regressions.",
+           comparison.changes.len(), 100.0 * tolerance.time, 100.0 * tolerance.effects,
+           comparison.regressions().len()).unwrap();
+  writeln!(writer, "({} is the mean, over the updates, of the median DCG compute time of each; effect counts are summed.)",
+           MEAN_TIME_METRIC).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// A sample of the lab `list-a`, with the columns that
+  /// `compare_data_rows` reads.
+  fn sample(size:&str, seed:&str, batch:&str, dcg_ns:u64, clean_eval:u64) -> DataRow {
+    let row : Vec<(&'static str, DataVal)> = vec![
+      ("lab",                  DataVal::Str(String::from("list-a"))),
+      ("seed",                 DataVal::Str(String::from(seed))),
+      ("batch_name",           DataVal::Str(String::from(batch))),
+      ("size",                 DataVal::Str(String::from(size))),
+      ("gauge",                DataVal::Nat(1)),
+      ("nominal_strategy",     DataVal::Str(String::from("regular"))),
+      ("demand",               DataVal::Nat(1)),
+      ("change_batch_size",    DataVal::Nat(1)),
+      ("change_batch_loopc",   DataVal::Nat(3)),
+      ("count_effects",        DataVal::Bool(true)),
+      ("dcg_compute_ns",       DataVal::Nat(dcg_ns)),
+      ("archivist_clean_eval", DataVal::Nat(clean_eval)),
+      ("archivist_dirty",      DataVal::Nat(0)),
+    ];
+    row.into_iter().map(|(col, v)| (String::from(col), v)).collect()
+  }
+
+  fn tolerance(time:f64, effects:f64) -> Tolerance { Tolerance{ time, effects } }
+
+  fn metric<'a>(c:&'a Comparison, metric:&str) -> &'a MetricChange {
+    c.changes.iter().find(|m| m.metric == metric).unwrap()
+  }
+
+  #[test]
+  fn matches_by_lab_point_seed_and_batch() {
+    let baseline = vec![sample("10", "0", "1", 100, 5), sample("10", "1", "1", 300, 5),
+                        sample("20", "0", "1", 1000, 5)];
+    // Seed 2 and size 30 are not in the baseline
+    let current  = vec![sample("10", "0", "1", 200, 5), sample("10", "1", "1", 400, 5),
+                        sample("10", "2", "1", 9000, 5), sample("30", "0", "1", 100, 5)];
+    let c = compare_data_rows(&baseline, &current, &tolerance(0.1, 0.0));
+    let time = metric(&c, MEAN_TIME_METRIC);
+    assert_eq!(time.samples, 2);
+    assert_eq!((time.baseline, time.current), (200.0, 300.0));
+    assert!(time.regression);
+    assert_eq!(c.unmatched.len(), 1);
+    assert_eq!(c.unmatched[0].0, "list-a");
+    assert!(c.unmatched[0].1.contains("size=30"));
+  }
+
+  #[test]
+  fn skips_the_initial_batch() {
+    let baseline = vec![sample("10", "0", "0", 100, 5), sample("10", "0", "1", 100, 5)];
+    let current  = vec![sample("10", "0", "0", 900, 50), sample("10", "0", "1", 100, 5)];
+    let c = compare_data_rows(&baseline, &current, &tolerance(0.1, 0.0));
+    assert_eq!(c.regressions().len(), 0);
+    assert_eq!(metric(&c, MEAN_TIME_METRIC).samples, 1);
+    // With only the initial batch, nothing matches
+    let c = compare_data_rows(&baseline, &vec![sample("10", "0", "0", 100, 5)], &tolerance(0.1, 0.0));
+    assert_eq!((c.changes.len(), c.unmatched.len()), (0, 1));
+  }
+
+  #[test]
+  fn zero_baseline() {
+    let baseline = vec![sample("10", "0", "1", 0, 0)];
+    let c = compare_data_rows(&baseline, &vec![sample("10", "0", "1", 100, 2)], &tolerance(0.1, 0.0));
+    let time = metric(&c, MEAN_TIME_METRIC);
+    assert!(time.regression);
+    assert_eq!(time.change(), ::std::f64::INFINITY);
+    assert_eq!(data_row_of_metric_change(time)[6], (String::from("change"), DataVal::Null));
+    assert_eq!(metric(&c, "archivist_clean_eval").change(), ::std::f64::INFINITY);
+    // Zero to zero is no change, and omits the effects that neither run has
+    let c = compare_data_rows(&baseline, &baseline, &tolerance(0.1, 0.0));
+    assert_eq!(metric(&c, MEAN_TIME_METRIC).change(), 0.0);
+    assert!(!c.changes.iter().any(|m| m.metric == "archivist_dirty" || m.metric == "archivist_clean_eval"));
+  }
+
+  #[test]
+  fn effect_count_tolerance() {
+    let baseline = vec![sample("10", "0", "1", 100, 100)];
+    let current  = vec![sample("10", "0", "1", 100, 104)];
+    // By default, any growth of an effect count is a regression
+    let c = compare_data_rows(&baseline, &current, &tolerance(0.1, 0.0));
+    assert!(metric(&c, "archivist_clean_eval").regression);
+    let c = compare_data_rows(&baseline, &current, &tolerance(0.1, 0.05));
+    assert!(!metric(&c, "archivist_clean_eval").regression);
+    let c = compare_data_rows(&baseline, &current, &tolerance(0.1, 0.03));
+    assert!(metric(&c, "archivist_clean_eval").regression);
+    // Fewer effects are never a regression
+    let c = compare_data_rows(&current, &baseline, &tolerance(0.1, 0.0));
+    assert_eq!(c.regressions().len(), 0);
+  }
+}
@@ -0,0 +1,253 @@
+use std::fs::{self,File};
+use std::io::prelude::*;
+use std::io::BufWriter;
+use toml::Value;
+use toml::value::Table;
+
+use labdef::{Preset,string_of_preset};
+
+/// The file of the effective configuration of a run, in its output
+/// directory (See `write_config`).
+pub const CONFIG_FILE : &'static str = "config.toml";
+
+/// One experiment of a configuration: a run of some labs, with the
+/// options of the `run` subcommand.
+#[derive(Clone,Debug,PartialEq)]
+pub struct Experiment {
+  /// The name of the experiment, which names the subdirectory of its
+  /// results (as `--name` does); None for a lone experiment.
+  pub name:    Option<String>,
+  /// The preset of the experiment (See `Preset`), e.g., `bench`.
+  pub preset:  Option<Preset>,
+  /// The options of `run`, by their long names (e.g., `size` for
+  /// `--size`), without the leading dashes.  A value is a string, a
+  /// number or a list of them, as on the command line (e.g., `size =
+  /// [1000, 10000]` for `--size 1000,10000`, or `seeds = "0..20"`),
+  /// or a boolean, for an option without a value (e.g., `isolate`).
+  pub options: Table,
+}
+
+fn string_of_item(key:&str, v:&Value) -> Result<String, String> {
+  match *v {
+    Value::String(ref s) => Ok(s.clone()),
+    Value::Integer(i) if i >= 0 => Ok(format!("{}", i)),
+    Value::Float(x) => Ok(format!("{}", x)),
+    _ => Err(format!("the option `{}` has the value `{}`; expected a string, a number or a boolean, or a list of them.", key, v)),
+  }
+}
+
+/// The command-line arguments of an option of an experiment, e.g.,
+/// `--size 1000,10000` for `size = [1000, 10000]`.  For an option
+/// without a value (`flag`), these are `--<key>` if its value is true,
+/// and nothing otherwise.
+pub fn args_of_option(key:&str, val:&Value, flag:bool) -> Result<Vec<String>, String> {
+  let arg = format!("--{}", key);
+  match *val {
+    Value::Boolean(b) if flag => Ok(if b { vec![arg] } else { vec![] }),
+    Value::Boolean(b) => Ok(vec![arg, format!("{}", b)]),
+    _ if flag => Err(format!("the option `{}` has no value; give true or false.", key)),
+    Value::Array(ref items) => {
+      let items : Result<Vec<String>, String> = items.iter().map(|v| string_of_item(key, v)).collect();
+      Ok(vec![arg, items?.join(",")])
+    },
+    _ => Ok(vec![arg, string_of_item(key, val)?]),
+  }
+}
+
+/// The command-line arguments of the options of an experiment (See
+/// `args_of_option`), given the long name of each option of `run`,
+/// and whether it has no value.  It is an error if an option is not
+/// one of these.
+pub fn args_of_options(options:&Table, run_options:&Vec<(&str, bool)>) -> Result<Vec<String>, String> {
+  let mut args = vec![];
+  for (key, val) in options.iter() {
+    match run_options.iter().find(|o| o.0 == key) {
+      None => return Err(format!("unknown option `{}`; expected an option of run (See run -h), `name` or `preset`.", key)),
+      Some(&(_, flag)) => args.append(&mut args_of_option(key, val, flag)?),
+    }
+  };
+  Ok(args)
+}
+
+/// An experiment from a table of a configuration, without its
+/// (optional) `name` and `preset`, which are not options of `run`.
+fn experiment_of_table(mut options:Table) -> Result<Experiment, String> {
+  let name = match options.remove("name") {
+    None => None,
+    Some(Value::String(s)) => Some(s),
+    Some(v) => return Err(format!("the name `{}` is not a string.", v)),
+  };
+  let preset = match options.remove("preset") {
+    None => None,
+    Some(Value::String(ref s)) if s == "run" => None,
+    Some(Value::String(s)) => Some(s.parse::<Preset>()?),
+    Some(v) => return Err(format!("the preset `{}` is not a string.", v)),
+  };
+  if options.contains_key("config") {
+    return Err(String::from("a configuration cannot give the option `config`."))
+  };
+  Ok(Experiment{ name, preset, options })
+}
+
+/// Parses a configuration, in TOML: its top-level keys are options
+/// for every experiment, and each table of the array `experiment`
+/// describes one experiment, with more options, which replace those
+/// of the top level.  Without that array, the top level is the only
+/// experiment.  Besides the options of `run` (See
+/// `Experiment::options`), a table may give the `name` of its
+/// experiment (which each of several experiments must give, and which
+/// must differ), and its `preset` (`run`, `viz` or `bench`).  For
+/// instance:
+///
+/// ```text
+/// seeds = "0..5"
+/// format = ["html", "csv"]
+///
+/// [[experiment]]
+/// name = "lazy-sizes"
+/// preset = "bench"
+/// lab = ["list-lazy-*"]
+/// size = [1000, 10000]
+///
+/// [[experiment]]
+/// name = "eager-batches"
+/// tag = ["eager"]
+/// batch = [1, 10, 100]
+/// loopc = 20
+/// ```
+pub fn read_config(s:&str) -> Result<Vec<Experiment>, String> {
+  let mut top = match s.parse::<Value>() {
+    Ok(Value::Table(t)) => t,
+    Ok(_) => return Err(String::from("expected a table.")),
+    Err(e) => return Err(format!("{}", e)),
+  };
+  let tables = match top.remove("experiment") {
+    None => return Ok(vec![experiment_of_table(top)?]),
+    Some(Value::Array(tables)) => tables,
+    Some(_) => return Err(String::from("expected `experiment` to be an array of tables, each given by `[[experiment]]`.")),
+  };
+  let mut experiments : Vec<Experiment> = vec![];
+  for table in tables.into_iter() {
+    let mut options = top.clone();
+    match table {
+      Value::Table(t) => options.extend(t.into_iter()),
+      _ => return Err(String::from("expected each experiment to be a table, given by `[[experiment]]`.")),
+    };
+    experiments.push(experiment_of_table(options)?)
+  };
+  if experiments.len() > 1 {
+    for (i, e) in experiments.iter().enumerate() {
+      match e.name {
+        None => return Err(format!("give each experiment a name; experiment {} has none.", i + 1)),
+        Some(ref name) if experiments[..i].iter().any(|f| f.name.as_ref() == Some(name)) =>
+          return Err(format!("the experiment name `{}` is not unique.", name)),
+        Some(_) => (),
+      }
+    }
+  };
+  if experiments.len() == 0 {
+    return Err(String::from("the array `experiment` is empty."))
+  };
+  Ok(experiments)
+}
+
+/// Reads a configuration from a file (See `read_config`).
+pub fn read_config_file(path:&str) -> Result<Vec<Experiment>, String> {
+  let mut s = String::new();
+  File::open(path).and_then(|mut f| f.read_to_string(&mut s)).map_err(|e| format!("{}: {}", path, e))?;
+  read_config(&s).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Writes an experiment to `<dir>/config.toml`, e.g., the effective
+/// configuration of a run, which `--config` reads back (See
+/// `read_config`).
+pub fn write_config(dir:&str, experiment:&Experiment) {
+  let mut table = experiment.options.clone();
+  match experiment.name {
+    None => (),
+    Some(ref name) => { table.insert(String::from("name"), Value::String(name.clone())); },
+  };
+  match experiment.preset {
+    None => (),
+    Some(ref preset) => { table.insert(String::from("preset"), Value::String(String::from(string_of_preset(preset)))); },
+  };
+  fs::create_dir_all(dir).unwrap();
+  let mut writer = BufWriter::new(File::create(format!("{}/{}", dir, CONFIG_FILE)).unwrap());
+  writeln!(writer, "# The effective configuration of this run; to run it again, give this file to --config.").unwrap();
+  writeln!(writer, "# The parameters that it does not give take the defaults of each lab (See the list subcommand).").unwrap();
+  write!(writer, "{}", Value::Table(table)).unwrap();
+  writer.flush().unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::env;
+
+  fn options() -> Vec<(&'static str, bool)> {
+    vec![("lab", false), ("size", false), ("seeds", false), ("tolerance", false), ("isolate", true)]
+  }
+
+  fn args(config:&str) -> Result<Vec<Vec<String>>, String> {
+    read_config(config)?.iter().map(|e| args_of_options(&e.options, &options())).collect()
+  }
+
+  #[test]
+  fn write_and_read() {
+    let config = "seeds = \"0..5\"\nisolate = true\n\
+                  [[experiment]]\nname = \"lazy\"\npreset = \"bench\"\nlab = [\"list-lazy-*\", \"!/sort/\"]\nsize = [1000, 10000]\n\
+                  [[experiment]]\nname = \"eager\"\npreset = \"run\"\ntolerance = 0.2\nisolate = false\n";
+    let experiments = read_config(config).unwrap();
+    assert_eq!(experiments.len(), 2);
+    assert_eq!(experiments[0].preset, Some(Preset::Bench));
+    assert_eq!(experiments[1].preset, None);
+    let dir = format!("{}/adapton-lab-test-config-{}", env::temp_dir().display(), ::std::process::id());
+    for experiment in experiments.iter() {
+      write_config(&dir, experiment);
+      assert_eq!(read_config_file(&format!("{}/{}", dir, CONFIG_FILE)), Ok(vec![experiment.clone()]));
+    };
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn args_of_experiments() {
+    assert_eq!(args("seeds = \"0..5\"\nisolate = true\nsize = [1000, 10000]\nlab = [\"a\", \"b\"]\ntolerance = 0.5\n"),
+               Ok(vec![vec!["--isolate", "--lab", "a,b", "--seeds", "0..5", "--size", "1000,10000", "--tolerance", "0.5"]
+                       .into_iter().map(String::from).collect()]));
+    assert_eq!(args("isolate = false\n"), Ok(vec![vec![]]));
+  }
+
+  #[test]
+  fn unknown_keys() {
+    assert_eq!(args("sizes = 10\n"),
+               Err(String::from("unknown option `sizes`; expected an option of run (See run -h), `name` or `preset`.")));
+    assert_eq!(args("[[experiment]]\nname = \"a\"\nlabs = \"x\"\n"),
+               Err(String::from("unknown option `labs`; expected an option of run (See run -h), `name` or `preset`.")));
+    assert_eq!(args("config = \"other.toml\"\n"),
+               Err(String::from("a configuration cannot give the option `config`.")));
+  }
+
+  #[test]
+  fn wrong_types() {
+    assert_eq!(args("size = -1\n"),
+               Err(String::from("the option `size` has the value `-1`; expected a string, a number or a boolean, or a list of them.")));
+    assert!(args("size = { min = 1 }\n").unwrap_err().starts_with("the option `size` has the value `"));
+    assert!(args("size = [[1], [2]]\n").unwrap_err().starts_with("the option `size` has the value `[1]`"));
+    assert_eq!(args("isolate = 1\n"), Err(String::from("the option `isolate` has no value; give true or false.")));
+    assert_eq!(args("isolate = \"yes\"\n"), Err(String::from("the option `isolate` has no value; give true or false.")));
+    assert_eq!(read_config("name = 3\n"), Err(String::from("the name `3` is not a string.")));
+    assert_eq!(read_config("preset = true\n"), Err(String::from("the preset `true` is not a string.")));
+    assert!(read_config("preset = \"fast\"\n").is_err());
+    assert_eq!(read_config("experiment = 3\n"),
+               Err(String::from("expected `experiment` to be an array of tables, each given by `[[experiment]]`.")));
+  }
+
+  #[test]
+  fn experiment_names() {
+    assert_eq!(read_config("[[experiment]]\nname = \"a\"\n[[experiment]]\nsize = 1\n"),
+               Err(String::from("give each experiment a name; experiment 2 has none.")));
+    assert_eq!(read_config("[[experiment]]\nname = \"a\"\n[[experiment]]\nname = \"a\"\n"),
+               Err(String::from("the experiment name `a` is not unique.")));
+    assert!(read_config("size = [1, \"a\"]\n").is_err());
+  }
+}
@@ -0,0 +1,679 @@
+use std::fs;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::str::FromStr;
+
+use adapton::reflect::string_of_name;
+use labdef::{LabParams,Lab,LabResults,Trajectory,Sample,EngineSample,EngineMetrics,TimingStats,EffectCounts,
+             LabFailure,EngineKind,string_of_nominal_strategy,lab_params};
+
+/// The formats in which we write lab results.  `Html` is the
+/// human-readable report of `labviz`, and `Dot` is the DCG graphs of
+/// `labdot`; the others are machine-readable tables, with one row per
+/// sample.
+#[derive(Clone,Debug,PartialEq)]
+pub enum OutputFormat {
+  Html,
+  Csv,
+  Tsv,
+  Json,
+  Dot,
+}
+
+/// Parses `html`, `csv`, `tsv`, `json` or `dot`, e.g., from the command line.
+impl FromStr for OutputFormat {
+  type Err = String;
+  fn from_str(s:&str) -> Result<OutputFormat, String> {
+    match s {
+      "html" => Ok(OutputFormat::Html),
+      "csv"  => Ok(OutputFormat::Csv),
+      "tsv"  => Ok(OutputFormat::Tsv),
+      "json" => Ok(OutputFormat::Json),
+      "dot"  => Ok(OutputFormat::Dot),
+      _ => Err(format!("unknown output format `{}`; expected `html`, `csv`, `tsv`, `json` or `dot`", s)),
+    }
+  }
+}
+
+/// The file extension for each output format.
+pub fn extension_of_format(format:&OutputFormat) -> &'static str {
+  match *format {
+    OutputFormat::Html => "html",
+    OutputFormat::Csv  => "csv",
+    OutputFormat::Tsv  => "tsv",
+    OutputFormat::Json => "json",
+    OutputFormat::Dot  => "dot",
+  }
+}
+
+/// A field of a data row.  We distinguish these cases so that the
+/// JSON output has numbers, booleans and nulls, not only strings.
+#[derive(Clone,Debug,PartialEq)]
+pub enum DataVal {
+  Nat(u64),
+  Num(f64),
+  Bool(bool),
+  Str(String),
+  Null,
+}
+
+/// A data row: a sequence of named fields, one row per sample.  Every
+/// row has the same field names, in the same order (See
+/// `data_row_of_sample`).
+pub type DataRow = Vec<(String, DataVal)>;
+
+/// Names each field of the effect counts, in a fixed order.
+pub fn fields_of_effect_counts(c:&EffectCounts) -> Vec<(&'static str, usize)> {
+  vec![
+    ("alloc_fresh_ref",   c.alloc_fresh_ref),
+    ("alloc_fresh_thunk", c.alloc_fresh_thunk),
+    ("alloc_same_ref",    c.alloc_same_ref),
+    ("alloc_same_thunk",  c.alloc_same_thunk),
+    ("alloc_diff_ref",    c.alloc_diff_ref),
+    ("alloc_diff_thunk",  c.alloc_diff_thunk),
+    ("force_cache_miss",  c.force_cache_miss),
+    ("force_cache_hit",   c.force_cache_hit),
+    ("force_ref_get",     c.force_ref_get),
+    ("dirty",             c.dirty),
+    ("clean_rec",         c.clean_rec),
+    ("clean_edge",        c.clean_edge),
+    ("clean_eval",        c.clean_eval),
+    ("remove",            c.remove),
+  ]
+}
+
+/// Flattens one sample into a data row, along with the lab name, the
+/// input seed of its trajectory and all of the lab parameters.
+pub fn data_row_of_sample(labname:&str, params:&LabParams, seed:usize, sample:&Sample) -> DataRow {
+  let sp = &params.sample_params;
+  let gp = &sp.generate_params;
+  let naive_edit = &sample.naive_sample.process_input.time_stats;
+  let dcg_edit   = &sample.dcg_sample.process_input.time_stats;
+  let naive      = &sample.naive_sample.compute_output.time_stats;
+  let dcg        = &sample.dcg_sample.compute_output.time_stats;
+  let row : Vec<(&'static str, DataVal)> = vec![
+    ("lab",                    DataVal::Str(String::from(labname))),
+    ("seed",                   DataVal::Nat(seed as u64)),
+    ("batch_name",             DataVal::Nat(sample.batch_name as u64)),
+    ("size",                   DataVal::Nat(gp.size as u64)),
+    ("gauge",                  DataVal::Nat(gp.gauge as u64)),
+    ("nominal_strategy",       DataVal::Str(String::from(string_of_nominal_strategy(&gp.nominal_strategy)))),
+    ("demand",                 DataVal::Nat(sp.demand as u64)),
+    ("change_batch_size",      DataVal::Nat(sp.change_batch_size as u64)),
+    ("change_batch_loopc",     DataVal::Nat(params.change_batch_loopc as u64)),
+    ("validate_output",        DataVal::Bool(sp.validate_output)),
+    ("reflect_trace",          DataVal::Bool(sp.reflect_trace)),
+    ("reflect_dcg",            DataVal::Bool(sp.reflect_dcg)),
+    ("timing_warmup",          DataVal::Nat(sp.timing_warmup as u64)),
+    ("timing_reps",            DataVal::Nat(sp.timing_reps as u64)),
+    ("count_effects",          DataVal::Bool(sp.count_effects)),
+    ("naive_editor_ns",        DataVal::Nat(naive_edit.median_ns)),
+    ("dcg_editor_ns",          DataVal::Nat(dcg_edit.median_ns)),
+    ("naive_compute_ns",       DataVal::Nat(naive.median_ns)),
+    ("naive_compute_min_ns",   DataVal::Nat(naive.min_ns)),
+    ("naive_compute_mean_ns",  DataVal::Num(naive.mean_ns)),
+    ("naive_compute_stddev_ns",DataVal::Num(naive.stddev_ns)),
+    ("naive_compute_p95_ns",   DataVal::Nat(naive.p95_ns)),
+    ("dcg_compute_ns",         DataVal::Nat(dcg.median_ns)),
+    ("dcg_compute_min_ns",     DataVal::Nat(dcg.min_ns)),
+    ("dcg_compute_mean_ns",    DataVal::Num(dcg.mean_ns)),
+    ("dcg_compute_stddev_ns",  DataVal::Num(dcg.stddev_ns)),
+    ("dcg_compute_p95_ns",     DataVal::Nat(dcg.p95_ns)),
+    ("output_valid",           match sample.output_valid {
+      None    => DataVal::Null,
+      Some(b) => DataVal::Bool(b),
+    }),
+  ];
+  let mut row : DataRow = row.into_iter().map(|(col, v)| (String::from(col), v)).collect();
+  for &(col, n) in fields_of_effect_counts(&sample.dcg_sample.process_input.effect_counts).iter() {
+    row.push((format!("editor_{}", col), DataVal::Nat(n as u64)))
+  };
+  for &(col, n) in fields_of_effect_counts(&sample.dcg_sample.compute_output.effect_counts).iter() {
+    row.push((format!("archivist_{}", col), DataVal::Nat(n as u64)))
+  };
+  row
+}
+
+/// Aggregates of the samples of one lab, for comparing the engines
+/// at a glance.  Times are in nanoseconds, using the median time of
+/// each sample (See `TimingStats`); each is None without samples.
+#[derive(Clone,Debug)]
+pub struct LabSummary {
+  pub labname:          String,
+  pub samples:          usize,
+  /// The mean time of the initial run (batch 0), which computes from scratch.
+  pub naive_initial_ns: Option<f64>,
+  pub dcg_initial_ns:   Option<f64>,
+  /// The mean time of the incremental updates (the later batches).
+  pub naive_update_ns:  Option<f64>,
+  pub dcg_update_ns:    Option<f64>,
+  /// The mean, over updates, of the ratio of the Naive time to the
+  /// DCG time: above 1, the DCG is faster.
+  pub speedup:          Option<f64>,
+  /// The ratio of the DCG's initial time to the Naive one: the
+  /// overhead of building the DCG.
+  pub overhead:         Option<f64>,
+  /// The number of samples whose outputs we compared.
+  pub validated:        usize,
+  /// The number of those samples whose outputs disagree.
+  pub mismatches:       usize,
+  pub failed:           bool,
+}
+
+fn mean(xs:&Vec<f64>) -> Option<f64> {
+  if xs.len() == 0 { None } else { Some(xs.iter().fold(0.0, |a, x| a + x) / (xs.len() as f64)) }
+}
+
+/// Summarizes the samples of a lab (See `LabSummary`).
+pub fn summarize_lab_results(lab:&Box<Lab>, results:&LabResults) -> LabSummary {
+  let mut samples = 0;
+  let (mut naive_initial, mut dcg_initial, mut naive_update, mut dcg_update, mut speedups) =
+    (vec![], vec![], vec![], vec![], vec![]);
+  let (mut validated, mut mismatches) = (0, 0);
+  for trajectory in results.trajectories.iter() {
+    for sample in trajectory.samples.iter() {
+      samples += 1;
+      let naive = sample.naive_sample.compute_output.time_stats.median_ns as f64;
+      let dcg   = sample.dcg_sample.compute_output.time_stats.median_ns as f64;
+      if sample.batch_name == 0 {
+        naive_initial.push(naive); dcg_initial.push(dcg);
+      } else {
+        naive_update.push(naive); dcg_update.push(dcg);
+        if dcg > 0.0 { speedups.push(naive / dcg) }
+      };
+      match sample.output_valid {
+        None => (),
+        Some(valid) => { validated += 1; if !valid { mismatches += 1 } },
+      }
+    }
+  };
+  let (naive_initial, dcg_initial) = (mean(&naive_initial), mean(&dcg_initial));
+  LabSummary{
+    labname:          string_of_name( &lab.name() ),
+    samples,
+    naive_initial_ns: naive_initial,
+    dcg_initial_ns:   dcg_initial,
+    naive_update_ns:  mean(&naive_update),
+    dcg_update_ns:    mean(&dcg_update),
+    speedup:          mean(&speedups),
+    overhead:         match (naive_initial, dcg_initial) {
+      (Some(n), Some(d)) if n > 0.0 => Some(d / n),
+      _ => None,
+    },
+    validated,
+    mismatches,
+    failed:           results.failures().len() > 0,
+  }
+}
+
+/// Flattens a lab summary into one row.
+pub fn data_row_of_lab_summary(s:&LabSummary) -> DataRow {
+  let num = |x:Option<f64>| match x { Some(x) => DataVal::Num(x), None => DataVal::Null };
+  let row : Vec<(&'static str, DataVal)> = vec![
+    ("lab",              DataVal::Str(s.labname.clone())),
+    ("samples",          DataVal::Nat(s.samples as u64)),
+    ("naive_initial_ns", num(s.naive_initial_ns)),
+    ("dcg_initial_ns",   num(s.dcg_initial_ns)),
+    ("naive_update_ns",  num(s.naive_update_ns)),
+    ("dcg_update_ns",    num(s.dcg_update_ns)),
+    ("speedup",          num(s.speedup)),
+    ("overhead",         num(s.overhead)),
+    ("validated",        DataVal::Nat(s.validated as u64)),
+    ("mismatches",       DataVal::Nat(s.mismatches as u64)),
+    ("failed",           DataVal::Bool(s.failed)),
+  ];
+  row.into_iter().map(|(col, v)| (String::from(col), v)).collect()
+}
+
+/// Writes the summary of each lab (See `LabSummary`), one row each,
+/// to `<out>/summary.<ext>`.
+pub fn write_lab_summaries(out:&str, format:&OutputFormat, labs:&Vec<Box<Lab>>, results:&Vec<LabResults>) {
+  assert!( labs.len() == results.len() );
+  let rows = labs.iter().zip(results.iter()).map(
+    |(lab, result)| data_row_of_lab_summary(&summarize_lab_results(lab, result))
+  ).collect();
+  fs::create_dir_all(out).unwrap();
+  let f = File::create(format!("{}/summary.{}", out, extension_of_format(format))).unwrap();
+  let mut writer = BufWriter::new(f);
+  write_data_rows(&mut writer, format, &rows);
+  writer.flush().unwrap();
+}
+
+/// Flattens the results of a lab into data rows, one per sample.
+pub fn data_rows_of_lab_results(params:&LabParams, lab:&Box<Lab>, results:&LabResults) -> Vec<DataRow> {
+  let labname = string_of_name( &lab.name() );
+  let mut rows = vec![];
+  for trajectory in results.trajectories.iter() {
+    for sample in trajectory.samples.iter() {
+      rows.push(data_row_of_sample(&labname, params, trajectory.seed, sample))
+    }
+  };
+  rows
+}
+
+/// The text of a field, as in CSV and TSV; empty for `Null`.
+pub fn string_of_data_val(v:&DataVal) -> String {
+  match *v {
+    DataVal::Nat(n)      => format!("{}", n),
+    DataVal::Num(n)      => format!("{}", n),
+    DataVal::Bool(b)     => format!("{}", b),
+    DataVal::Str(ref s)  => s.clone(),
+    DataVal::Null        => String::new(),
+  }
+}
+
+fn csv_field(s:String) -> String {
+  if s.contains(',') || s.contains('"') || s.contains('\n') || s.contains('\r') {
+    format!("\"{}\"", s.replace("\"", "\"\""))
+  } else { s }
+}
+
+fn tsv_field(s:String) -> String {
+  s.replace('\t', " ").replace('\n', " ").replace('\r', " ")
+}
+
+fn json_string(s:&str) -> String {
+  let mut out = String::from("\"");
+  for c in s.chars() {
+    match c {
+      '"'  => out.push_str("\\\""),
+      '\\' => out.push_str("\\\\"),
+      '\n' => out.push_str("\\n"),
+      '\r' => out.push_str("\\r"),
+      '\t' => out.push_str("\\t"),
+      c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+      c => out.push(c),
+    }
+  };
+  out.push('"');
+  out
+}
+
+fn json_of_data_val(v:&DataVal) -> String {
+  match *v {
+    DataVal::Nat(n)      => format!("{}", n),
+    // JSON has no representation for NaN or infinities
+    DataVal::Num(n)      => if n.is_finite() { format!("{}", n) } else { String::from("null") },
+    DataVal::Bool(b)     => format!("{}", b),
+    DataVal::Str(ref s)  => json_string(s),
+    DataVal::Null        => String::from("null"),
+  }
+}
+
+/// Writes data rows in a machine-readable format.  For CSV and TSV,
+/// the first line names the columns.  For JSON, we write an array of
+/// objects, one per row.
+pub fn write_data_rows<W:Write>(writer:&mut W, format:&OutputFormat, rows:&Vec<DataRow>) {
+  match *format {
+    OutputFormat::Html => panic!("write_data_rows: use labviz to write HTML"),
+    OutputFormat::Dot  => panic!("write_data_rows: use labdot to write DOT"),
+    OutputFormat::Csv | OutputFormat::Tsv => {
+      let (sep, field) : (&str, fn(String) -> String) =
+        if *format == OutputFormat::Csv { (",", csv_field) } else { ("\t", tsv_field) };
+      match rows.first() {
+        None => (),
+        Some(row) => {
+          let header : Vec<_> = row.iter().map(|&(ref col, _)| field(col.clone())).collect();
+          writeln!(writer, "{}", header.join(sep)).unwrap();
+        }
+      };
+      for row in rows.iter() {
+        let fields : Vec<_> = row.iter().map(|&(_, ref v)| field(string_of_data_val(v))).collect();
+        writeln!(writer, "{}", fields.join(sep)).unwrap();
+      }
+    },
+    OutputFormat::Json => {
+      writeln!(writer, "[").unwrap();
+      for (i, row) in rows.iter().enumerate() {
+        let fields : Vec<_> = row.iter().map(
+          |&(ref col, ref v)| format!("{}:{}", json_string(col), json_of_data_val(v))
+        ).collect();
+        writeln!(writer, "  {{{}}}{}", fields.join(","), if i + 1 < rows.len() { "," } else { "" }).unwrap();
+      };
+      writeln!(writer, "]").unwrap();
+    },
+  }
+}
+
+/// Splits a line of CSV into its fields, undoing `csv_field`.
+fn csv_fields(line:&str) -> Vec<String> {
+  let mut fields = vec![];
+  let mut field  = String::new();
+  let mut quoted = false;
+  let mut chars  = line.chars().peekable();
+  while let Some(c) = chars.next() {
+    match c {
+      '"' if quoted && chars.peek() == Some(&'"') => { field.push('"'); chars.next(); },
+      '"'              => quoted = !quoted,
+      ',' if !quoted   => fields.push(::std::mem::replace(&mut field, String::new())),
+      c                => field.push(c),
+    }
+  };
+  fields.push(field);
+  fields
+}
+
+/// Reads data rows written by `write_data_rows` as CSV or TSV, e.g.,
+/// an exported result set.  The columns are named by the first line.
+/// The types of the fields are lost: Each is a string, or `Null` if
+/// empty.  In CSV, a quoted field may span lines.
+pub fn read_data_rows<R:BufRead>(reader:R, format:&OutputFormat) -> Result<Vec<DataRow>, String> {
+  let split = |line:&str| -> Vec<String> {
+    match *format {
+      OutputFormat::Csv => csv_fields(line),
+      _ => line.split('\t').map(String::from).collect(),
+    }
+  };
+  match *format {
+    OutputFormat::Csv | OutputFormat::Tsv => (),
+    _ => return Err(format!("can only read csv or tsv, not {}", extension_of_format(format))),
+  };
+  let mut header = None;
+  let mut rows = vec![];
+  let mut pending = String::new();
+  let mut lines = 0;
+  for (i, line) in reader.lines().enumerate() {
+    lines = i + 1;
+    let line = line.map_err(|e| format!("line {}: {}", i + 1, e))?;
+    // Allow Windows line endings, e.g., from a spreadsheet
+    let line = line.trim_end_matches('\r');
+    if pending.len() > 0 { pending.push('\n') };
+    pending.push_str(line);
+    // An odd number of quotes leaves a field open, until a later line
+    if *format == OutputFormat::Csv && pending.matches('"').count() % 2 == 1 { continue };
+    let line = ::std::mem::replace(&mut pending, String::new());
+    if line.len() == 0 { continue };
+    let fields = split(&line);
+    match header {
+      None => header = Some(fields),
+      Some(ref cols) => {
+        if fields.len() != cols.len() {
+          return Err(format!("line {}: expected {} fields, not {}", i + 1, cols.len(), fields.len()))
+        };
+        rows.push(cols.iter().cloned().zip(fields.into_iter().map(
+          |f| if f.len() == 0 { DataVal::Null } else { DataVal::Str(f) }
+        )).collect())
+      }
+    }
+  };
+  if pending.len() > 0 {
+    return Err(format!("line {}: a quoted field does not end", lines))
+  };
+  Ok(rows)
+}
+
+/// Reads data rows from a file (See `read_data_rows`), in the format
+/// given by its extension (`.csv` or `.tsv`).
+pub fn read_data_rows_file(path:&str) -> Result<Vec<DataRow>, String> {
+  let ext = Path::new(path).extension().and_then(|e| e.to_str()).unwrap_or("");
+  let format = ext.parse::<OutputFormat>().map_err(|e| format!("{}: {}", path, e))?;
+  let f = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+  read_data_rows(BufReader::new(f), &format).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Writes the samples of one lab to `<output_dir>/<labname>/samples.<ext>`.
+pub fn write_lab_data(format:&OutputFormat, params:&LabParams, lab:&Box<Lab>, results:&LabResults) {
+  let labname = string_of_name( &lab.name() );
+  let rows = data_rows_of_lab_results(params, lab, results);
+  fs::create_dir_all(format!("{}/{}/", params.output_dir, labname)).unwrap();
+  let f = File::create(format!("{}/{}/samples.{}", params.output_dir, labname, extension_of_format(format))).unwrap();
+  let mut writer = BufWriter::new(f);
+  write_data_rows(&mut writer, format, &rows);
+  writer.flush().unwrap();
+}
+
+/// Writes the samples of all labs, together, to
+/// `<output_dir>/samples.<ext>`, and the summary of each lab to
+/// `<output_dir>/summary.<ext>` (See `write_lab_summaries`).
+pub fn write_lab_data_summary(format:&OutputFormat, params:&LabParams, labs:&Vec<Box<Lab>>, results:&Vec<LabResults>) {
+  assert!( labs.len() == results.len() );
+  let mut rows = vec![];
+  for (lab, result) in labs.iter().zip(results.iter()) {
+    rows.append(&mut data_rows_of_lab_results(&lab_params(params, lab.info()), lab, result))
+  };
+  fs::create_dir_all(&params.output_dir).unwrap();
+  let f = File::create(format!("{}/samples.{}", params.output_dir, extension_of_format(format))).unwrap();
+  let mut writer = BufWriter::new(f);
+  write_data_rows(&mut writer, format, &rows);
+  writer.flush().unwrap();
+  write_lab_summaries(&params.output_dir, format, labs, results);
+}
+
+/// The text of a field of a data row read by `read_data_rows`.
+fn field_of_data_row<'a>(row:&'a DataRow, col:&str) -> Result<&'a DataVal, String> {
+  match row.iter().find(|&&(ref c, _)| c == col) {
+    None => Err(format!("no field `{}`", col)),
+    Some(&(_, ref v)) => Ok(v),
+  }
+}
+
+fn parse_field<T:FromStr>(row:&DataRow, col:&str) -> Result<T, String> {
+  let s = string_of_data_val(field_of_data_row(row, col)?);
+  s.parse::<T>().map_err(|_| format!("couldn't parse the field `{}`: `{}`", col, s))
+}
+
+/// The effect counts of a data row, from its fields `<prefix>_<effect>`
+/// (See `fields_of_effect_counts`).
+fn effect_counts_of_data_row(row:&DataRow, prefix:&str) -> Result<EffectCounts, String> {
+  let n = |effect:&str| parse_field::<usize>(row, &format!("{}_{}", prefix, effect));
+  Ok(EffectCounts{
+    alloc_fresh_ref:   n("alloc_fresh_ref")?,
+    alloc_fresh_thunk: n("alloc_fresh_thunk")?,
+    alloc_same_ref:    n("alloc_same_ref")?,
+    alloc_same_thunk:  n("alloc_same_thunk")?,
+    alloc_diff_ref:    n("alloc_diff_ref")?,
+    alloc_diff_thunk:  n("alloc_diff_thunk")?,
+    force_cache_miss:  n("force_cache_miss")?,
+    force_cache_hit:   n("force_cache_hit")?,
+    force_ref_get:     n("force_ref_get")?,
+    dirty:             n("dirty")?,
+    clean_rec:         n("clean_rec")?,
+    clean_edge:        n("clean_edge")?,
+    clean_eval:        n("clean_eval")?,
+    remove:            n("remove")?,
+  })
+}
+
+/// The timing statistics of a data row, from its fields
+/// `<prefix>_ns`, `<prefix>_min_ns`, and so on; with `median_only`,
+/// from `<prefix>_ns` alone (e.g., for the editor).
+fn time_stats_of_data_row(row:&DataRow, prefix:&str, median_only:bool) -> Result<TimingStats, String> {
+  let count = parse_field(row, "timing_reps")?;
+  let median_ns = parse_field(row, &format!("{}_ns", prefix))?;
+  if median_only {
+    return Ok(TimingStats{ count, min_ns:median_ns, median_ns, mean_ns:median_ns as f64, stddev_ns:0.0, p95_ns:median_ns })
+  };
+  Ok(TimingStats{
+    count,
+    min_ns:    parse_field(row, &format!("{}_min_ns", prefix))?,
+    median_ns,
+    mean_ns:   parse_field(row, &format!("{}_mean_ns", prefix))?,
+    stddev_ns: parse_field(row, &format!("{}_stddev_ns", prefix))?,
+    p95_ns:    parse_field(row, &format!("{}_p95_ns", prefix))?,
+  })
+}
+
+fn engine_metrics(time_stats:TimingStats, effect_counts:EffectCounts) -> EngineMetrics {
+  EngineMetrics{
+    time_ns:         time_stats.median_ns,
+    time_ns_samples: vec![],
+    time_stats,
+    batch_times_ns:  vec![],
+    effect_counts,
+    reflect_traces:  vec![],
+    reflect_dcg:     None,
+  }
+}
+
+/// The sample of a data row written by `data_row_of_sample` (and read
+/// by `read_data_rows`), with the input seed of its trajectory.  The
+/// sample has only what the row records: the timing statistics
+/// (only the median for the editor), the effect counts of the DCG
+/// engine and the validity of the output; it has no inputs, outputs,
+/// traces or DCGs.
+pub fn sample_of_data_row(row:&DataRow) -> Result<(usize, Sample), String> {
+  let output_valid = match *field_of_data_row(row, "output_valid")? {
+    DataVal::Null => None,
+    _ => Some(parse_field(row, "output_valid")?),
+  };
+  let sample = Sample{
+    batch_name:   parse_field(row, "batch_name")?,
+    dcg_sample:   EngineSample{
+      process_input:  engine_metrics(time_stats_of_data_row(row, "dcg_editor", true)?,
+                                     effect_counts_of_data_row(row, "editor")?),
+      compute_output: engine_metrics(time_stats_of_data_row(row, "dcg_compute", false)?,
+                                     effect_counts_of_data_row(row, "archivist")?),
+      input: None, output: None,
+    },
+    naive_sample: EngineSample{
+      process_input:  engine_metrics(time_stats_of_data_row(row, "naive_editor", true)?, EffectCounts::default()),
+      compute_output: engine_metrics(time_stats_of_data_row(row, "naive_compute", false)?, EffectCounts::default()),
+      input: None, output: None,
+    },
+    output_valid,
+    output_mismatch: None,
+  };
+  Ok((parse_field(row, "seed")?, sample))
+}
+
+/// Reads the samples of one lab written by `write_lab_data` as CSV
+/// (e.g., by a child process; See `labproc`), as trajectories, one
+/// per input seed, in order (See `sample_of_data_row`).  No
+/// trajectories if there is no such file (e.g., the lab crashed).
+pub fn read_lab_trajectories(out:&str, labname:&str) -> Result<Vec<Trajectory>, String> {
+  let path = format!("{}/{}/samples.{}", out, labname, extension_of_format(&OutputFormat::Csv));
+  if !Path::new(&path).exists() { return Ok(vec![]) };
+  let mut trajectories : Vec<Trajectory> = vec![];
+  for (i, row) in read_data_rows_file(&path)?.iter().enumerate() {
+    let (seed, sample) = sample_of_data_row(row).map_err(|e| format!("{}: row {}: {}", path, i + 1, e))?;
+    match trajectories.iter().position(|t| t.seed == seed) {
+      Some(j) => trajectories[j].samples.push(sample),
+      None => trajectories.push(Trajectory{ seed, samples: vec![sample], failure: None, edit_script: None }),
+    }
+  };
+  Ok(trajectories)
+}
+
+/// The path of the failures of a lab (See `write_lab_failures`).
+pub fn lab_failures_path(out:&str, labname:&str) -> String {
+  format!("{}/{}/failures.tsv", out, labname)
+}
+
+/// Writes the failures of a lab to `<output_dir>/<labname>/failures.tsv`,
+/// one per line: the input seed, the batch name, the engine and the
+/// message, with empty fields for `None`.  Removes this file if the
+/// lab has no failures.
+pub fn write_lab_failures(params:&LabParams, lab:&Box<Lab>, results:&LabResults) {
+  let labname = string_of_name( &lab.name() );
+  let path = lab_failures_path(&params.output_dir, &labname);
+  let failures = results.failures();
+  if failures.len() == 0 {
+    let _ = fs::remove_file(path);
+    return
+  };
+  fs::create_dir_all(format!("{}/{}/", params.output_dir, labname)).unwrap();
+  let mut writer = BufWriter::new(File::create(path).unwrap());
+  for f in failures.iter() {
+    writeln!(writer, "{}\t{}\t{}\t{}",
+             match f.seed { Some(s) => format!("{}", s), None => String::new() },
+             match f.batch_name { Some(b) => format!("{}", b), None => String::new() },
+             match f.engine { Some(ref e) => format!("{:?}", e), None => String::new() },
+             tsv_field(f.message.clone())).unwrap();
+  };
+  writer.flush().unwrap();
+}
+
+/// Reads the failures of a lab written by `write_lab_failures`; no
+/// failures if there is no such file.
+pub fn read_lab_failures(out:&str, labname:&str) -> Vec<LabFailure> {
+  let path = lab_failures_path(out, labname);
+  if !Path::new(&path).exists() { return vec![] };
+  let f = BufReader::new(File::open(path).unwrap());
+  let mut failures = vec![];
+  for line in f.lines() {
+    let line = line.unwrap();
+    let fields : Vec<_> = line.splitn(4, '\t').collect();
+    if fields.len() < 4 { continue };
+    failures.push(LabFailure{
+      seed:       fields[0].parse().ok(),
+      batch_name: fields[1].parse().ok(),
+      engine:     match fields[2] { "Naive" => Some(EngineKind::Naive), "DCG" => Some(EngineKind::DCG), _ => None },
+      message:    String::from(fields[3]),
+    })
+  };
+  failures
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn row(fields:Vec<(&str, DataVal)>) -> DataRow {
+    fields.into_iter().map(|(col, v)| (String::from(col), v)).collect()
+  }
+
+  fn str(s:&str) -> DataVal { DataVal::Str(String::from(s)) }
+
+  fn write_and_read(format:&OutputFormat, rows:&Vec<DataRow>) -> Result<Vec<DataRow>, String> {
+    let mut buf = vec![];
+    write_data_rows(&mut buf, format, rows);
+    read_data_rows(&buf[..], format)
+  }
+
+  #[test]
+  fn csv_round_trip() {
+    let rows = vec![
+      row(vec![("lab", str("a,b")), ("note", str("say \"hi\"")), ("n", DataVal::Nat(3))]),
+      row(vec![("lab", str("two\nlines")), ("note", str("a\ttab")), ("n", DataVal::Num(0.5))]),
+      row(vec![("lab", str("\"")), ("note", DataVal::Bool(true)), ("n", DataVal::Null)]),
+    ];
+    assert_eq!(write_and_read(&OutputFormat::Csv, &rows), Ok(vec![
+      row(vec![("lab", str("a,b")), ("note", str("say \"hi\"")), ("n", str("3"))]),
+      row(vec![("lab", str("two\nlines")), ("note", str("a\ttab")), ("n", str("0.5"))]),
+      row(vec![("lab", str("\"")), ("note", str("true")), ("n", DataVal::Null)]),
+    ]));
+  }
+
+  #[test]
+  fn tsv_round_trip() {
+    // TSV cannot quote, so tabs and newlines become spaces
+    let rows = vec![
+      row(vec![("lab", str("a,b \"c\"")), ("note", str("a\ttab\nand\r\nlines"))]),
+      row(vec![("lab", DataVal::Null), ("note", DataVal::Nat(7))]),
+    ];
+    assert_eq!(write_and_read(&OutputFormat::Tsv, &rows), Ok(vec![
+      row(vec![("lab", str("a,b \"c\"")), ("note", str("a tab and  lines"))]),
+      row(vec![("lab", DataVal::Null), ("note", str("7"))]),
+    ]));
+  }
+
+  #[test]
+  fn empty_fields_are_null() {
+    let rows = vec![row(vec![("a", str("")), ("b", DataVal::Null), ("c", str("x"))])];
+    let nulls = Ok(vec![row(vec![("a", DataVal::Null), ("b", DataVal::Null), ("c", str("x"))])]);
+    assert_eq!(write_and_read(&OutputFormat::Csv, &rows), nulls);
+    assert_eq!(write_and_read(&OutputFormat::Tsv, &rows), nulls);
+    assert_eq!(read_data_rows("a,b\n,\n".as_bytes(), &OutputFormat::Csv),
+               Ok(vec![row(vec![("a", DataVal::Null), ("b", DataVal::Null)])]));
+  }
+
+  #[test]
+  fn csv_fields_unquote() {
+    assert_eq!(csv_fields("a,\"b,c\",\"d\"\"e\",,f"), vec!["a", "b,c", "d\"e", "", "f"]);
+    assert_eq!(csv_fields(""), vec![""]);
+  }
+
+  #[test]
+  fn read_data_rows_errors() {
+    assert_eq!(read_data_rows("a,b\n1,2\n1,2,3\n".as_bytes(), &OutputFormat::Csv),
+               Err(String::from("line 3: expected 2 fields, not 3")));
+    assert_eq!(read_data_rows("a\tb\tc\n1\t2\n".as_bytes(), &OutputFormat::Tsv),
+               Err(String::from("line 2: expected 3 fields, not 2")));
+    assert_eq!(read_data_rows("a,b\n\"1,2\n".as_bytes(), &OutputFormat::Csv),
+               Err(String::from("line 2: a quoted field does not end")));
+    assert!(read_data_rows("[]".as_bytes(), &OutputFormat::Json).is_err());
+  }
+}
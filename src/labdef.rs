@@ -1,26 +1,83 @@
 //use adapton::engine::Cnt; // Counters for engine costs
-use adapton::engine::Name; // Names, for naming things uniquely
+use adapton::engine::{Name,name_of_str,name_of_hash64,name_pair}; // Names, for naming things uniquely
 use adapton::reflect::DCG;
 use adapton::reflect::Val;
 use adapton::reflect::trace::Trace;
 use rand::Rng;
 use std::marker::PhantomData;
+use std::str::FromStr;
+use std::fmt;
+use std::hash::{Hash,Hasher};
+use std::collections::hash_map::DefaultHasher;
 
 /// A bit that controls how names are placed in the input; See `README.md` for more.
-#[derive(Clone,Debug)]
+#[derive(Clone,Debug,PartialEq)]
 pub enum NominalStrategy {
   Regular,
   ByContent,
 }
 
+/// Parses `regular` or `bycontent`, e.g., from the command line.
+impl FromStr for NominalStrategy {
+  type Err = String;
+  fn from_str(s:&str) -> Result<NominalStrategy, String> {
+    match s {
+      "regular"   => Ok(NominalStrategy::Regular),
+      "bycontent" => Ok(NominalStrategy::ByContent),
+      _ => Err(format!("unknown nominal strategy `{}`; expected `regular` or `bycontent`", s)),
+    }
+  }
+}
+
+/// The inverse of parsing (See `FromStr`).
+pub fn string_of_nominal_strategy(n:&NominalStrategy) -> &'static str {
+  match *n {
+    NominalStrategy::Regular   => "regular",
+    NominalStrategy::ByContent => "bycontent",
+  }
+}
+
+/// The greatest input size (See `GenerateParams::size`): Editors draw
+/// the elements of an input below `100 * size`.
+pub const MAX_SIZE : usize = ::std::usize::MAX / 100;
+
 /// Parameters for generating and editing input; See `README.md` for more.
-#[derive(Clone,Debug)]
+#[derive(Clone,Debug,PartialEq)]
 pub struct GenerateParams {
   pub size: usize, 
   pub gauge: usize, 
   pub nominal_strategy:NominalStrategy
 }
 
+/// Names an articulation by the hash of some content, for
+/// `NominalStrategy::ByContent`.
+pub fn name_of_content<T:Hash>(content:&T) -> Name {
+  let mut hasher = DefaultHasher::new();
+  content.hash(&mut hasher);
+  name_of_hash64(hasher.finish())
+}
+
+/// Names an element of a fixed example list.  Under
+/// `NominalStrategy::Regular`, we use the given name; under
+/// `NominalStrategy::ByContent`, we hash the element itself.
+pub fn name_of_elm(params:&GenerateParams, name:&'static str, elm:usize) -> Name {
+  match params.nominal_strategy {
+    NominalStrategy::Regular   => name_of_str(name),
+    NominalStrategy::ByContent => name_of_content(&elm),
+  }
+}
+
+/// Names the ref cell of an element of a fixed example list, as
+/// `name_of_elm` does, except that under `NominalStrategy::ByContent`,
+/// we pair the hash with `cell`, so that it differs from the name of
+/// the element itself.
+pub fn name_of_cell(params:&GenerateParams, name:&'static str, elm:usize) -> Name {
+  match params.nominal_strategy {
+    NominalStrategy::Regular   => name_of_str(name),
+    NominalStrategy::ByContent => name_pair(name_of_str("cell"), name_of_content(&elm)),
+  }
+}
+
 /// Generic method for generating a random input.
 /// See `README.md` for more.
 pub trait Generate<T> {
@@ -32,6 +89,21 @@ pub trait Generate<T> {
 pub trait Edit<T,S> : Clone {
   fn edit_init<R:Rng>(rng:&mut R, params:&GenerateParams) -> S;
   fn edit<R:Rng>(pre_edit:T, edit_state:S, rng:&mut R, params:&GenerateParams) -> (T, S);
+
+  /// Like `edit`, but also describes the edit, as one line of text,
+  /// for an edit script (See `EditScript`).  From this description,
+  /// `replay_edit` must reproduce the edit, without the Rng.  Editors
+  /// that do not override this method do not describe their edits
+  /// (None), and we cannot record them.
+  fn edit_recorded<R:Rng>(pre_edit:T, edit_state:S, rng:&mut R, params:&GenerateParams) -> (T, S, Option<String>) {
+    let (t, s) = Self::edit(pre_edit, edit_state, rng, params);
+    (t, s, None)
+  }
+
+  /// Reproduces an edit from its description (See `edit_recorded`).
+  fn replay_edit(_pre_edit:T, _edit_state:S, edit:&str, _params:&GenerateParams) -> (T, S) {
+    panic!("this Editor cannot replay the recorded edit `{}`", edit)
+  }
 }
 
 /// Generic notion of a computation to run naively and incrementally.
@@ -64,6 +136,8 @@ pub struct LabDef<Input,EditSt,Output,
 {
   pub identity:  Name,
   pub url:       Option<String>,
+  pub info:      LabInfo,
+  pub types:     LabTypes,
 
   pub editor:    PhantomData<Editor>,
   pub archivist: PhantomData<Archivist>,
@@ -73,11 +147,185 @@ pub struct LabDef<Input,EditSt,Output,
   pub output:    PhantomData<Output>
 }
 
+/// The optional facts about a lab, beyond its name and its URL (See
+/// `labdef!`).
+#[derive(Clone,Debug,Default)]
+pub struct LabInfo {
+  /// What the lab computes, and what it shows, for people.
+  pub description: &'static str,
+  /// Words that describe the lab (e.g., `list` or `lazy`), for
+  /// selecting labs (See `--tag`).
+  pub tags:        Vec<&'static str>,
+  /// Whether the Archivist is lazy, i.e., whether it uses the
+  /// `demand` (See `ComputeDemand`).
+  pub lazy:        bool,
+  /// The lab's own defaults for some of its parameters, e.g., for an
+  /// Editor that ignores the input size (See `lab_params`).
+  pub defaults:    ParamsOverride,
+  /// The lab's own changes to the viz preset (See `Preset::Viz`),
+  /// e.g., a larger input, to show something.
+  pub viz:         ParamsOverride,
+  /// The lab's own changes to the bench preset (See `Preset::Bench`),
+  /// e.g., a smaller input, for an Archivist that recurs over the
+  /// whole list.
+  pub bench:       ParamsOverride,
+}
+
+impl LabInfo {
+  /// The lab's own changes to a preset.
+  pub fn preset(&self, preset:&Preset) -> &ParamsOverride {
+    match *preset {
+      Preset::Viz   => &self.viz,
+      Preset::Bench => &self.bench,
+    }
+  }
+}
+
+/// A preset of the lab parameters (See `labmain::apply_preset`),
+/// which each lab may change (See `LabInfo::preset`), and which the
+/// user's options then change.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum Preset {
+  /// For visualization: a small input size, with reflection.
+  Viz,
+  /// For benchmarking: a large input size, without reflection or
+  /// effect counts, which add overhead to the DCG times.
+  Bench,
+}
+
+/// Parses `viz` or `bench`, e.g., from a configuration (See
+/// `labconfig`).
+impl FromStr for Preset {
+  type Err = String;
+  fn from_str(s:&str) -> Result<Preset, String> {
+    match s {
+      "viz"   => Ok(Preset::Viz),
+      "bench" => Ok(Preset::Bench),
+      _ => Err(format!("unknown preset `{}`; expected `run`, `viz` or `bench`", s)),
+    }
+  }
+}
+
+/// The inverse of parsing (See `FromStr`); also the name of the
+/// subcommand of the preset.
+pub fn string_of_preset(p:&Preset) -> &'static str {
+  match *p {
+    Preset::Viz   => "viz",
+    Preset::Bench => "bench",
+  }
+}
+
+/// The types of a `LabDef`, as written in its `labdef!`.
+#[derive(Clone,Debug)]
+pub struct LabTypes {
+  pub input:     &'static str,
+  pub editst:    &'static str,
+  pub output:    &'static str,
+  pub editor:    &'static str,
+  pub archivist: &'static str,
+}
+
+/// New values for some of the parameters of a lab (See `LabParams`),
+/// leaving the others; each is either a lab's default (See
+/// `LabInfo::defaults`) or the user's choice (See
+/// `LabParams::overrides`).
+#[derive(Clone,Debug,Default,PartialEq,Eq)]
+pub struct ParamsOverride {
+  pub size:               Option<usize>,
+  pub gauge:              Option<usize>,
+  pub change_batch_size:  Option<usize>,
+  pub demand:             Option<usize>,
+  pub change_batch_loopc: Option<usize>,
+}
+
+impl ParamsOverride {
+  pub fn is_empty(&self) -> bool { *self == ParamsOverride::default() }
+
+  /// Sets the parameters that this gives.
+  pub fn apply(&self, params:&mut LabParams) {
+    let sp = &mut params.sample_params;
+    if let Some(x) = self.size               { sp.generate_params.size  = x };
+    if let Some(x) = self.gauge              { sp.generate_params.gauge = x };
+    if let Some(x) = self.change_batch_size  { sp.change_batch_size     = x };
+    if let Some(x) = self.demand             { sp.demand                = x };
+    if let Some(x) = self.change_batch_loopc { params.change_batch_loopc = x };
+  }
+}
+
+/// Shows the parameters that this gives, by the names of their
+/// command-line options, e.g., `size=1 loopc=3`.
+impl fmt::Display for ParamsOverride {
+  fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result {
+    let params = [("size", self.size), ("artfreq", self.gauge), ("batch", self.change_batch_size),
+                  ("demand", self.demand), ("loopc", self.change_batch_loopc)];
+    let given : Vec<String> = params.iter().filter_map(|&(name, x)| x.map(|x| format!("{}={}", name, x))).collect();
+    write!(f, "{}", given.join(" "))
+  }
+}
+
+/// The parameters of a lab: the given parameters, changed by the
+/// lab's defaults (See `LabInfo::defaults`), then by the lab's own
+/// changes to the preset, if any (See `LabParams::preset`), and then
+/// by the user's choices (See `LabParams::overrides`), which win.
+/// The runners (See `labmain::run_lab`) run each lab with these;
+/// `Lab::run` itself runs the parameters that it gets.
+pub fn lab_params(params:&LabParams, info:&LabInfo) -> LabParams {
+  let mut p = params.clone();
+  info.defaults.apply(&mut p);
+  match params.preset { Some(ref preset) => info.preset(preset).apply(&mut p), None => () };
+  params.overrides.apply(&mut p);
+  p
+}
+
+/// Builds a boxed `Lab` from a `LabDef`, given its name, its URL
+/// (e.g., to its documentation), and its types: the input, the
+/// Editor's state, the output, the Editor and the Archivist.  After a
+/// semicolon, the fields of its `LabInfo` may follow (e.g., `tags`),
+/// each as `field: value`, separated by semicolons.  For instance,
+/// from `catalog::all_labs`:
+///
+/// ```text
+/// labdef!(name_of_str("list-eager-map"),
+///         Some(String::from("...")),
+///         List<usize>, usize,
+///         List<usize>,
+///         UniformPrepend<_,_>,
+///         EagerMap;
+///         tags: vec!["list", "eager", "map"])
+/// ```
+#[macro_export]
+macro_rules! labdef {
+  ( $name:expr, $url:expr, $inp:ty, $editst:ty, $out:ty, $dist:ty, $comp:ty $( ; $field:ident : $val:expr )* ) => {{ 
+    Box::new( 
+      $crate::labdef::LabDef
+        ::<$inp,$editst,$out,$dist,$comp>
+      { 
+        identity:$name,
+        url:$url,
+        info:$crate::labdef::LabInfo{ $( $field:$val, )* ..::std::default::Default::default() },
+        types:$crate::labdef::LabTypes{
+          input:     stringify!($inp),
+          editst:    stringify!($editst),
+          output:    stringify!($out),
+          editor:    stringify!($dist),
+          archivist: stringify!($comp),
+        },
+        input: ::std::marker::PhantomData,
+        editst: ::std::marker::PhantomData,
+        output: ::std::marker::PhantomData,
+        editor: ::std::marker::PhantomData,
+        archivist: ::std::marker::PhantomData
+      }) 
+  }}
+}
+
 /// _lab_: Abstracts over parts of a lab definition of type `LabDef`:
 /// Hides the `Input`, `Output` and `Archivist` types of a `LabDef`.
 pub trait Lab {
   fn name(self:&Self) -> Name;
   fn url(self:&Self) -> &Option<String>;
+  fn info(self:&Self) -> &LabInfo;
+  fn types(self:&Self) -> &LabTypes;
   fn run(self:&Self, params:&LabParams) -> LabResults;
 }
 
@@ -88,18 +336,50 @@ pub struct LabParams {
   // TODO: Pretty-print input and output structures; graphmovie dump of experiment
   /// Number of change-batches to perform in a loop; each is interposed with computing the new output.
   pub change_batch_loopc: usize,
+  /// Instead of editing randomly with `Edit::edit`, replay the edits
+  /// of this script (See `Edit::replay_edit`), for its input seed
+  /// only; the script also determines the change batches.
+  pub replay_edits: Option<EditScript>,
+  /// The directory in which we write the results (e.g.,
+  /// `lab-results`), with a subdirectory for each lab.
+  pub output_dir: String,
+  /// The parameters that the user chose (e.g., on the command line),
+  /// which the defaults of a lab do not replace (See `lab_params`).
+  pub overrides: ParamsOverride,
+  /// The preset of these parameters, if any, which each lab may
+  /// change (See `LabInfo::preset`).
+  pub preset: Option<Preset>,
+}
+
+/// A recorded trajectory of edits, which we can replay exactly,
+/// even after the `Edit` implementation (and hence, its use of the
+/// Rng) changes.  The initial input is still generated from the input
+/// seed (See `Generate`).  See `labscript` for its file format.
+#[derive(Clone,Debug,PartialEq)]
+pub struct EditScript {
+  /// The name of the lab whose Editor recorded the edits.
+  pub labname:         String,
+  /// The input seed, for generating the initial input.
+  pub seed:            usize,
+  /// The parameters for generating the initial input, and for editing.
+  pub generate_params: GenerateParams,
+  /// The edits of each change batch, in order, starting with batch 1
+  /// (batch 0 generates the initial input).  Each edit is described
+  /// by `Edit::edit_recorded`.
+  pub batches:         Vec<Vec<String>>,
 }
 
 /// Parameters for collecting a single sample.  In addition to these
-/// parameters, the experiment maintains a Rng based on the
+/// parameters, the experiment maintains a Rng based on one of the
 /// input_seeds, below; this Rng is given to Edit::edit to generate
 /// psuedo-random edits, in batches.  For each engine, this Rng is
-/// sequenced across successive samples.  Given an input_seeds vector,
-/// there is one unique Rng sequence for each engine's sequence of
-/// samples.
+/// sequenced across successive samples.  Given an input seed, there
+/// is one unique Rng sequence for each engine's sequence of samples.
 #[derive(Clone,Debug)]
 pub struct SampleParams {
-  /// We convert this seed into a random-number-generator before generating and editing.
+  /// We convert each seed into a random-number-generator before
+  /// generating and editing; each seed gives an independent
+  /// trajectory of samples, each with a fresh DCG.
   pub input_seeds:       Vec<usize>, 
   /// Other parameters for generating the input.
   pub generate_params:   GenerateParams, 
@@ -117,12 +397,95 @@ pub struct SampleParams {
   /// Reflect the DCG (See `adapton::engine::reflect::DCG`), including
   /// its values (See `adapton::engine::reflect_val`).
   pub reflect_dcg: bool,
+  /// Number of untimed runs of the naive computation before we time
+  /// it, for each sample.
+  pub timing_warmup: usize,
+  /// Number of timed runs for each sample.  We re-run the naive
+  /// computation over the same input.  Since we cannot re-run a DCG
+  /// update in place, we replay each sample on `timing_reps - 1`
+  /// additional DCGs, each of which follows the same trajectory.
+  pub timing_reps: usize,
+  /// Count the trace effects of each sample (See `EffectCounts`).
+  /// When `reflect_trace` is false, we still trace each sample to
+  /// count its effects, but we do not keep the trace; this tracing
+  /// adds overhead to the DCG's timings.
+  pub count_effects: bool,
+  /// Record the edits of each trajectory, as an edit script (See
+  /// `Trajectory::edit_script`).
+  pub record_edits: bool,
 }
 
-/// The result of a lab is a sequence of samples.
+/// A minimal counterexample to from-scratch consistency, found by
+/// shrinking a failing lab run (See `labrun::shrink_failure`).
+#[derive(Clone,Debug)]
+pub struct ShrinkResult {
+  /// The shrunk parameters, with only the failing input seed; running
+  /// the lab with these parameters reproduces the failure.
+  pub params:     LabParams,
+  /// The failing input seed.
+  pub seed:       usize,
+  /// The first sample whose DCG and naive outputs disagree, under
+  /// the shrunk parameters.
+  pub batch_name: usize,
+  /// The number of lab runs that the search performed.
+  pub runs:       usize,
+}
+
+/// The result of a lab is a trajectory for each input seed.
 #[derive(Clone,Debug)]
 pub struct LabResults {
-  pub samples: Vec<Sample>
+  pub trajectories: Vec<Trajectory>,
+  /// A failure of the lab as a whole, outside of any one trajectory;
+  /// e.g., its child process crashed or timed out (See `labproc`).
+  pub failure: Option<LabFailure>,
+}
+
+impl LabResults {
+  /// The failures of the lab: at most one per trajectory, and at most
+  /// one for the lab as a whole.
+  pub fn failures(&self) -> Vec<&LabFailure> {
+    let mut failures : Vec<_> = self.trajectories.iter().filter_map(|t| t.failure.as_ref()).collect();
+    match self.failure { Some(ref f) => failures.push(f), None => () };
+    failures
+  }
+}
+
+/// A trajectory is the sequence of samples that we collect from one
+/// input seed, starting from a fresh DCG.
+#[derive(Clone,Debug)]
+pub struct Trajectory {
+  pub seed:    usize,
+  pub samples: Vec<Sample>,
+  /// If the lab panicked, the trajectory ends early, with this
+  /// failure; its samples are those that completed before the panic.
+  pub failure: Option<LabFailure>,
+  /// The recorded edits of the trajectory, including those of a
+  /// failing sample; None unless `SampleParams::record_edits` is set
+  /// and the Editor describes its edits (See `Edit::edit_recorded`).
+  pub edit_script: Option<EditScript>,
+}
+
+/// The engines that we sample.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum EngineKind {
+  Naive,
+  DCG,
+}
+
+/// A lab failure: the Editor or the Archivist panicked (e.g., with a
+/// run-time type error from a misuse of nominal memoization), or the
+/// lab's child process crashed or timed out.
+#[derive(Clone,Debug)]
+pub struct LabFailure {
+  /// The panic message, or a description of how the child process ended.
+  pub message:    String,
+  /// The engine in use when the lab panicked; None if the panic
+  /// happened outside of sampling an engine.
+  pub engine:     Option<EngineKind>,
+  /// The input seed of the failing trajectory, if known.
+  pub seed:       Option<usize>,
+  /// The batch name of the failing sample, if any.
+  pub batch_name: Option<usize>,
 }
 
 /// The experiment consists of a loop over samples.  For each sample,
@@ -136,7 +499,10 @@ pub struct Sample {
   pub batch_name:   usize,   // Index/name the change batches; one sample per compute + change batch
   pub dcg_sample:   EngineSample,
   pub naive_sample: EngineSample,
-  pub output_valid: Option<bool>
+  pub output_valid: Option<bool>,
+  /// When the outputs disagree, the `Debug` text of the DCG output
+  /// and of the naive output, in that order.
+  pub output_mismatch: Option<(String, String)>,
 }
 
 /// To sample a single engine, we record metrics for processing the
@@ -156,11 +522,23 @@ pub struct EngineSample {
 /// real time (in nanoseconds) and engine-based counters for DCG costs.
 #[derive(Clone,Debug)]
 pub struct EngineMetrics {
-  /// Time in milliseconds.
+  /// The time (in nanoseconds) of the first timed run of this sample.
   pub time_ns:    u64,
+  /// The time (in nanoseconds) of each timed run of this sample,
+  /// including the first run, whose time is `time_ns`.  See
+  /// `SampleParams::timing_reps`.
+  pub time_ns_samples: Vec<u64>,
+  /// Summary statistics of `time_ns_samples`.
+  pub time_stats: TimingStats,
+  /// For the editor, the time (in nanoseconds) of each edit in the
+  /// change batch, in the order applied; `time_ns` is the time for
+  /// the entire batch.  Empty when generating the initial input, and
+  /// for the archivist.
+  pub batch_times_ns: Vec<u64>,
   /// Counts of engine (engine trace-level) operations (See
-  /// `engine::reflect::trace::Trace`) during the sample.
-//  pub engine_cnt: Cnt,
+  /// `engine::reflect::trace::Trace`) during the sample; all zero if
+  /// `SampleParams::count_effects` is disabled.
+  pub effect_counts: EffectCounts,
   /// The reflected traces of the DCG during the sample; empty if this
   /// option is disabled.
   pub reflect_traces: Vec<Trace>,
@@ -168,3 +546,162 @@ pub struct EngineMetrics {
   /// sample; None if this option is disabled.
   pub reflect_dcg: Option<DCG>,
 }
+
+/// Counts of each kind of trace effect (See
+/// `adapton::reflect::trace::Effect`) during a sample, over the
+/// entire trace tree.  Unlike times, these counts are deterministic:
+/// they measure the work of the DCG without machine noise.
+#[derive(Clone,Debug,Default,PartialEq,Eq)]
+pub struct EffectCounts {
+  /// `Alloc(LocFresh, RefCell)`
+  pub alloc_fresh_ref:     usize,
+  /// `Alloc(LocFresh, Thunk)`
+  pub alloc_fresh_thunk:   usize,
+  /// `Alloc(LocExists(ContentSame), RefCell)`
+  pub alloc_same_ref:      usize,
+  /// `Alloc(LocExists(ContentSame), Thunk)`
+  pub alloc_same_thunk:    usize,
+  /// `Alloc(LocExists(ContentDiff), RefCell)`
+  pub alloc_diff_ref:      usize,
+  /// `Alloc(LocExists(ContentDiff), Thunk)`
+  pub alloc_diff_thunk:    usize,
+  /// `Force(CompCacheMiss)`
+  pub force_cache_miss:    usize,
+  /// `Force(CompCacheHit)`
+  pub force_cache_hit:     usize,
+  /// `Force(RefGet)`
+  pub force_ref_get:       usize,
+  pub dirty:               usize,
+  pub clean_rec:           usize,
+  pub clean_edge:          usize,
+  pub clean_eval:          usize,
+  pub remove:              usize,
+}
+
+/// Summary statistics over the timed runs of a sample, in nanoseconds.
+#[derive(Clone,Debug)]
+pub struct TimingStats {
+  /// Number of timed runs.
+  pub count:     usize,
+  pub min_ns:    u64,
+  pub median_ns: u64,
+  pub mean_ns:   f64,
+  pub stddev_ns: f64,
+  /// 95th percentile (nearest rank).
+  pub p95_ns:    u64,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::rc::Rc;
+  use adapton::catalog::collections::*;
+  use adapton::engine::{cell,ns};
+  use labtest::{consistency_params,check_consistency};
+
+  fn params(nominal_strategy:NominalStrategy) -> GenerateParams {
+    GenerateParams{ size:0, gauge:1, nominal_strategy }
+  }
+
+  #[test]
+  fn names_by_content_are_distinct() {
+    let p = params(NominalStrategy::ByContent);
+    // An element and its ref cell
+    assert!(name_of_cell(&p, "b", 1) != name_of_elm(&p, "beta", 1));
+    // Two elements, and two ref cells
+    assert!(name_of_elm(&p, "beta", 1) != name_of_elm(&p, "gamma", 2));
+    assert!(name_of_cell(&p, "b", 1) != name_of_cell(&p, "c", 2));
+    assert!(name_of_cell(&p, "b", 1) != name_of_elm(&p, "gamma", 2));
+    // The given names do not matter, only the element
+    assert_eq!(name_of_elm(&p, "beta", 1), name_of_elm(&p, "b", 1));
+    assert_eq!(name_of_cell(&p, "b", 1), name_of_cell(&p, "beta", 1));
+    assert_eq!(name_of_elm(&p, "beta", 1), name_of_content(&1usize));
+  }
+
+  #[test]
+  fn regular_names_are_the_given_names() {
+    let p = params(NominalStrategy::Regular);
+    assert_eq!(name_of_elm(&p, "beta", 1), name_of_str("beta"));
+    assert_eq!(name_of_cell(&p, "b", 1), name_of_str("b"));
+    assert_eq!(name_of_elm(&p, "beta", 2), name_of_str("beta"));
+  }
+
+  /// The regular names of the elements below 16, and of their ref cells.
+  const ELM_NAMES  : [&'static str; 16] = ["alpha", "beta", "gamma", "delta", "epsilon", "zeta", "eta", "theta",
+                                           "iota", "kappa", "lambda", "mu", "nu", "xi", "omicron", "pi"];
+  const CELL_NAMES : [&'static str; 16] = ["a", "b", "c", "d", "e", "f", "g", "h",
+                                           "i", "j", "k", "l", "m", "n", "o", "p"];
+
+  /// The list of the elements, each followed by its name and its ref
+  /// cell, as the examples of the catalog build them.
+  fn list_of_elms(params:&GenerateParams, elms:&Vec<usize>) -> List<usize> {
+    let mut l = list_nil();
+    for &elm in elms.iter().rev() {
+      l = list_art(cell(name_of_cell(params, CELL_NAMES[elm], elm), l));
+      l = list_name(name_of_elm(params, ELM_NAMES[elm], elm), l);
+      l = list_cons(elm, l);
+    };
+    l
+  }
+
+  /// Starts with the even elements below 16, and then either inserts
+  /// or removes a random element, rebuilding the list around it.
+  #[derive(Clone,Debug)]
+  struct ElmEditor { }
+
+  impl Generate<List<usize>> for ElmEditor {
+    fn generate<R:Rng>(rng:&mut R, params:&GenerateParams) -> List<usize> {
+      list_of_elms(params, &Self::edit_init(rng, params))
+    }
+  }
+
+  impl Edit<List<usize>,Vec<usize>> for ElmEditor {
+    fn edit_init<R:Rng>(_rng:&mut R, _params:&GenerateParams) -> Vec<usize> {
+      (0..ELM_NAMES.len()).filter(|x| x % 2 == 0).collect()
+    }
+    fn edit<R:Rng>(_l:List<usize>, mut elms:Vec<usize>, rng:&mut R, params:&GenerateParams) -> (List<usize>, Vec<usize>) {
+      let x = rng.gen_range(0, ELM_NAMES.len());
+      match elms.binary_search(&x) {
+        Ok(i)  => { elms.remove(i); },
+        Err(i) => elms.insert(i, x),
+      };
+      (list_of_elms(params, &elms), elms)
+    }
+  }
+
+  /// Sums the list by way of a tree, whose articulations take their
+  /// names from the list.
+  #[derive(Clone,Debug)]
+  struct TreeSum { }
+
+  impl Compute<List<usize>,usize> for TreeSum {
+    fn compute(inp:List<usize>) -> usize {
+      let tree : Tree<usize> = ns(name_of_str("tree_of_list"), move|| tree_of_list(Dir2::Left, inp));
+      monoid_of_tree(tree, 0, Rc::new(|x, y| x + y))
+    }
+  }
+
+  #[test]
+  fn names_by_content_are_consistent() {
+    let lab : Box<Lab> = labdef!(name_of_str("elm-tree-sum"), None,
+                                 List<usize>, Vec<usize>, usize, ElmEditor, TreeSum);
+    for nominal_strategy in vec![NominalStrategy::Regular, NominalStrategy::ByContent] {
+      let mut p = consistency_params((0..10).collect(), 10);
+      p.sample_params.generate_params.nominal_strategy = nominal_strategy;
+      assert_eq!(check_consistency(&lab, &p), Ok(()));
+      // The DCG and naive outputs agree after each edit; to compare
+      // them here, we reflect them
+      p.sample_params.reflect_dcg = true;
+      let results = lab.run(&lab_params(&p, lab.info()));
+      assert_eq!(results.failures().len(), 0);
+      for t in results.trajectories.iter() {
+        assert_eq!(t.samples.len(), 11);
+        for s in t.samples.iter() {
+          assert!(s.dcg_sample.output.is_some());
+          assert_eq!(s.dcg_sample.output, s.naive_sample.output, "seed {}, batch {}", t.seed, s.batch_name);
+          assert_eq!(s.output_valid, Some(true));
+        }
+      }
+    }
+  }
+}
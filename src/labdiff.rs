@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+
+use adapton::reflect::*;
+
+/// A structural difference between two reflected DCGs, `before` and
+/// `after` (See `diff_dcg`).  Edges are identified by their source
+/// node, their effect and their target node; each edge is given as a
+/// pair of its source location and its (reflected) `Succ`, as found in
+/// the `after` DCG, except for removed edges, which are as found in
+/// the `before` DCG.  To make diffs stable, each list is sorted by
+/// location.
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct DcgDiff {
+  /// Nodes in `after`, but not in `before`
+  pub nodes_added:        Vec<Loc>,
+  /// Nodes in `before`, but not in `after`
+  pub nodes_removed:      Vec<Loc>,
+  /// Thunks in both whose outgoing edges (ignoring their dirty bits)
+  /// or whose value changed, i.e., thunks that change propagation
+  /// (re-)executed.  A thunk that re-executes with exactly the same
+  /// effects and value is indistinguishable from one that did not
+  /// re-execute, and is not listed.
+  pub nodes_reevaluated:  Vec<Loc>,
+  /// Nodes in both whose (reflected) value changed
+  pub values_changed:     Vec<Loc>,
+  /// Edges in `after`, but not in `before`
+  pub edges_added:        Vec<(Loc, Succ)>,
+  /// Edges in `before`, but not in `after`
+  pub edges_removed:      Vec<(Loc, Succ)>,
+  /// Edges in both that are clean in `before` and dirty in `after`
+  pub edges_dirtied:      Vec<(Loc, Succ)>,
+  /// Edges in both that are dirty in `before` and clean in `after`
+  pub edges_cleaned:      Vec<(Loc, Succ)>,
+}
+
+impl DcgDiff {
+  /// True if the two DCGs are structurally equal, up to the dirty
+  /// bits of their edges.
+  pub fn is_empty(&self) -> bool {
+    self.nodes_added.len() == 0 && self.nodes_removed.len() == 0 &&
+      self.nodes_reevaluated.len() == 0 && self.values_changed.len() == 0 &&
+      self.edges_added.len() == 0 && self.edges_removed.len() == 0 &&
+      self.edges_dirtied.len() == 0 && self.edges_cleaned.len() == 0
+  }
+}
+
+/// The value of a DCG node, if any; thunks that have not yet been
+/// executed have no value.
+pub fn value_of_node(nd:&Node) -> Option<&Val> {
+  match *nd {
+    Node::Comp(ref nd) => nd.value.as_ref(),
+    Node::Ref(ref nd)  => Some(&nd.value),
+    Node::Pure(ref nd) => Some(&nd.value),
+  }
+}
+
+/// Compares the outgoing edges of two nodes, ignoring dirty bits.
+fn succs_eq_modulo_dirty(s1:&Vec<Succ>, s2:&Vec<Succ>) -> bool {
+  s1.len() == s2.len() &&
+    s1.iter().zip(s2.iter()).all(
+      |(e1, e2)| e1.loc == e2.loc && e1.effect == e2.effect && e1.value == e2.value && e1.is_dup == e2.is_dup
+    )
+}
+
+/// Indexes the edges of a DCG by source, effect and target.
+/// Duplicate edges carry no further information, so we keep the first
+/// edge for each key.
+fn edges_of_dcg(dcg:&DCG) -> HashMap<(Loc, Effect, Loc), &Succ> {
+  let mut edges = HashMap::new();
+  for (loc, nd) in dcg.table.iter() {
+    match succs_of_node(nd) {
+      None => (),
+      Some(succs) => for succ in succs.iter() {
+        edges.entry((loc.clone(), succ.effect.clone(), succ.loc.clone())).or_insert(succ);
+      }
+    }
+  };
+  edges
+}
+
+fn sort_locs(locs:&mut Vec<Loc>) {
+  locs.sort_by(|l1, l2| string_of_loc(l1).cmp(&string_of_loc(l2)))
+}
+
+fn sort_edges(edges:&mut Vec<(Loc, Succ)>) {
+  edges.sort_by(|&(ref s1, ref e1), &(ref s2, ref e2)|
+                (string_of_loc(s1), string_of_loc(&e1.loc), format!("{:?}", e1.effect))
+                .cmp(&(string_of_loc(s2), string_of_loc(&e2.loc), format!("{:?}", e2.effect))))
+}
+
+/// Computes the structural difference from DCG `before` to DCG
+/// `after`.  E.g., from the DCG after an edit to the DCG after the
+/// following update, this diff shows what change propagation touched.
+pub fn diff_dcg(before:&DCG, after:&DCG) -> DcgDiff {
+  let mut diff = DcgDiff{
+    nodes_added:vec![], nodes_removed:vec![], nodes_reevaluated:vec![], values_changed:vec![],
+    edges_added:vec![], edges_removed:vec![], edges_dirtied:vec![], edges_cleaned:vec![],
+  };
+  for (loc, nd_after) in after.table.iter() {
+    match before.table.get(loc) {
+      None => diff.nodes_added.push(loc.clone()),
+      Some(nd_before) => {
+        if value_of_node(nd_before) != value_of_node(nd_after) {
+          diff.values_changed.push(loc.clone())
+        };
+        match (nd_before, nd_after) {
+          (&Node::Comp(ref c1), &Node::Comp(ref c2)) => {
+            if c1.value != c2.value || !succs_eq_modulo_dirty(&c1.succs, &c2.succs) {
+              diff.nodes_reevaluated.push(loc.clone())
+            }
+          },
+          _ => (),
+        }
+      }
+    }
+  };
+  for loc in before.table.keys() {
+    if !after.table.contains_key(loc) {
+      diff.nodes_removed.push(loc.clone())
+    }
+  };
+  let edges_before = edges_of_dcg(before);
+  let edges_after  = edges_of_dcg(after);
+  for (key, succ_after) in edges_after.iter() {
+    match edges_before.get(key) {
+      None => diff.edges_added.push((key.0.clone(), (*succ_after).clone())),
+      Some(succ_before) => {
+        if !succ_before.dirty && succ_after.dirty {
+          diff.edges_dirtied.push((key.0.clone(), (*succ_after).clone()))
+        } else if succ_before.dirty && !succ_after.dirty {
+          diff.edges_cleaned.push((key.0.clone(), (*succ_after).clone()))
+        }
+      }
+    }
+  };
+  for (key, succ_before) in edges_before.iter() {
+    if !edges_after.contains_key(key) {
+      diff.edges_removed.push((key.0.clone(), (*succ_before).clone()))
+    }
+  };
+  sort_locs(&mut diff.nodes_added);
+  sort_locs(&mut diff.nodes_removed);
+  sort_locs(&mut diff.nodes_reevaluated);
+  sort_locs(&mut diff.values_changed);
+  sort_edges(&mut diff.edges_added);
+  sort_edges(&mut diff.edges_removed);
+  sort_edges(&mut diff.edges_dirtied);
+  sort_edges(&mut diff.edges_cleaned);
+  diff
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use adapton::engine::name_of_str;
+  use adapton::macros::ProgPt;
+
+  fn loc(name:&'static str) -> Loc { Loc{ path:vec![], name:name_of_str(name) } }
+
+  fn nat(n:usize) -> Val { Val::Const(Const::Nat(n)) }
+
+  fn succ(dirty:bool, effect:Effect, target:&'static str, value:Val) -> Succ {
+    Succ{ dirty, loc:loc(target), effect, value, is_dup:false }
+  }
+
+  fn comp(succs:Vec<Succ>, value:Option<Val>) -> Node {
+    Node::Comp(CompNode{ preds:vec![], succs, prog_pt:ProgPt{ symbol:"f" }, value })
+  }
+
+  fn cell(value:Val) -> Node { Node::Ref(RefNode{ preds:vec![], value }) }
+
+  fn dcg(nodes:Vec<(&'static str, Node)>) -> DCG {
+    DCG{ table:nodes.into_iter().map(|(name, nd)| (loc(name), nd)).collect(), stack:vec![], path:vec![] }
+  }
+
+  /// Thunk `t` forces cell `c`, with the given values and dirty bit.
+  fn force_cell(c:usize, t:usize, dirty:bool) -> DCG {
+    dcg(vec![("c", cell(nat(c))),
+             ("t", comp(vec![succ(dirty, Effect::Force, "c", nat(c))], Some(nat(t))))])
+  }
+
+  #[test]
+  fn no_change() {
+    let diff = diff_dcg(&force_cell(1, 1, false), &force_cell(1, 1, false));
+    assert!(diff.is_empty());
+    assert_eq!(diff, DcgDiff{
+      nodes_added:vec![], nodes_removed:vec![], nodes_reevaluated:vec![], values_changed:vec![],
+      edges_added:vec![], edges_removed:vec![], edges_dirtied:vec![], edges_cleaned:vec![],
+    });
+  }
+
+  #[test]
+  fn dirtied_edges() {
+    // An edit of `c` dirties its incoming edge; nothing re-executes
+    let before = force_cell(1, 1, false);
+    let mut after = force_cell(1, 1, true);
+    after.table.insert(loc("c"), cell(nat(2)));
+    let diff = diff_dcg(&before, &after);
+    assert_eq!(diff.values_changed, vec![loc("c")]);
+    assert_eq!(diff.edges_dirtied, vec![(loc("t"), succ(true, Effect::Force, "c", nat(1)))]);
+    assert_eq!(diff.nodes_reevaluated, vec![]);
+    assert_eq!((diff.nodes_added.len(), diff.nodes_removed.len()), (0, 0));
+    assert_eq!((diff.edges_added.len(), diff.edges_removed.len(), diff.edges_cleaned.len()), (0, 0, 0));
+  }
+
+  #[test]
+  fn cleaned_edges_and_changed_values() {
+    // Re-executing `t` cleans its edge, and observes the new value of `c`
+    let mut before = force_cell(1, 1, true);
+    before.table.insert(loc("c"), cell(nat(2)));
+    let after = force_cell(2, 2, false);
+    let diff = diff_dcg(&before, &after);
+    // The edit already changed `c`
+    assert_eq!(diff.values_changed, vec![loc("t")]);
+    assert_eq!(diff.nodes_reevaluated, vec![loc("t")]);
+    assert_eq!(diff.edges_cleaned, vec![(loc("t"), succ(false, Effect::Force, "c", nat(2)))]);
+    assert_eq!(diff.edges_dirtied, vec![]);
+    assert_eq!((diff.nodes_added.len(), diff.nodes_removed.len()), (0, 0));
+    assert_eq!((diff.edges_added.len(), diff.edges_removed.len()), (0, 0));
+  }
+
+  #[test]
+  fn added_and_removed_nodes_and_edges() {
+    // Thunk `t` allocates `b` instead of `a`, and forces `c` in both
+    let before = dcg(vec![("a", cell(nat(1))), ("c", cell(nat(0))),
+                          ("t", comp(vec![succ(false, Effect::Alloc, "a", nat(1)),
+                                          succ(false, Effect::Force, "c", nat(0))], Some(nat(1))))]);
+    let after  = dcg(vec![("b", cell(nat(1))), ("c", cell(nat(0))),
+                          ("t", comp(vec![succ(false, Effect::Alloc, "b", nat(1)),
+                                          succ(false, Effect::Force, "c", nat(0))], Some(nat(1))))]);
+    let diff = diff_dcg(&before, &after);
+    assert_eq!(diff.nodes_added,   vec![loc("b")]);
+    assert_eq!(diff.nodes_removed, vec![loc("a")]);
+    assert_eq!(diff.edges_added,   vec![(loc("t"), succ(false, Effect::Alloc, "b", nat(1)))]);
+    assert_eq!(diff.edges_removed, vec![(loc("t"), succ(false, Effect::Alloc, "a", nat(1)))]);
+    // Its value is the same, but its effects differ
+    assert_eq!(diff.nodes_reevaluated, vec![loc("t")]);
+    assert_eq!(diff.values_changed, vec![]);
+    assert_eq!((diff.edges_dirtied.len(), diff.edges_cleaned.len()), (0, 0));
+  }
+
+  #[test]
+  fn edges_differ_by_effect() {
+    // Forcing a cell that `t` allocated adds a new edge to the same target
+    let before = dcg(vec![("a", cell(nat(1))),
+                          ("t", comp(vec![succ(false, Effect::Alloc, "a", nat(1))], None))]);
+    let after  = dcg(vec![("a", cell(nat(1))),
+                          ("t", comp(vec![succ(false, Effect::Alloc, "a", nat(1)),
+                                          succ(false, Effect::Force, "a", nat(1))], Some(nat(1))))]);
+    let diff = diff_dcg(&before, &after);
+    assert_eq!(diff.edges_added, vec![(loc("t"), succ(false, Effect::Force, "a", nat(1)))]);
+    assert_eq!(diff.edges_removed, vec![]);
+    // A thunk's first value is a change of value
+    assert_eq!(diff.values_changed, vec![loc("t")]);
+    assert_eq!(diff.nodes_reevaluated, vec![loc("t")]);
+  }
+}
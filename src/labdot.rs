@@ -0,0 +1,124 @@
+use std::fs;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::fs::File;
+
+use adapton::reflect::*;
+use labdef::{LabParams,Lab,LabResults,EngineMetrics};
+
+/// Quotes a string as a DOT identifier.
+fn dot_string(s:&str) -> String {
+  format!("\"{}\"", s.replace("\\", "\\\\").replace("\"", "\\\"").replace("\n", "\\n"))
+}
+
+/// The (quoted) DOT identifier of a DCG node.
+pub fn dot_id_of_loc(loc:&Loc) -> String {
+  dot_string(&string_of_loc(loc))
+}
+
+/// The DOT attributes of a DCG node: computations (thunks) are boxes,
+/// reference cells are ellipses and pure values are plain text.
+/// Thunks that have not yet been executed are dashed.
+pub fn dot_attrs_of_node(loc:&Loc, nd:&Node) -> String {
+  let loc = string_of_loc(loc);
+  match *nd {
+    Node::Comp(ref nd) =>
+      format!("shape=box, label={}{}", dot_string(&loc),
+              if nd.value == None { ", style=dashed" } else { "" }),
+    Node::Ref(_)  => format!("shape=ellipse, label={}", dot_string(&loc)),
+    Node::Pure(_) => format!("shape=plaintext, label={}", dot_string(&loc)),
+  }
+}
+
+/// The DOT attributes of a DCG edge: forces are solid and allocations
+/// are dashed; dirty edges are red, and duplicate edges are grey.
+pub fn dot_attrs_of_succ(succ:&Succ) -> String {
+  format!("style={}, color={}, arrowhead={}",
+          match succ.effect { Effect::Force => "solid", Effect::Alloc => "dashed" },
+          if succ.dirty { "red" } else if succ.is_dup { "grey" } else { "black" },
+          match succ.effect { Effect::Force => "normal", Effect::Alloc => "odot" })
+}
+
+/// Writes a reflected DCG as a DOT graph.  Unlike the nested `div`s
+/// of `labviz`, each node appears exactly once, so sharing is
+/// explicit.  For the Editor's phase, the traces give the roots of the
+/// graph: its top-level effects appear as edges from a distinguished
+/// `editor` node.  We skip the other traces: backward edges (e.g.,
+/// dirtying) and edges from a node are already edges of the DCG, and
+/// the Archivist's top-level effects are not the Editor's.  To make
+/// the output stable for diffing, we sort nodes by location.
+pub fn write_dcg_dot<W:Write>(writer:&mut W, graph_name:&str, dcg:&DCG, role:trace::Role, traces:&Vec<trace::Trace>) {
+  writeln!(writer, "digraph {} {{", dot_string(graph_name)).unwrap();
+  writeln!(writer, "  node [fontname=\"monospace\", fontsize=10];").unwrap();
+  let traces = match role {
+    trace::Role::Editor => {
+      writeln!(writer, "  \"editor\" [shape=doublecircle, label=\"editor\"];").unwrap();
+      &traces[..]
+    },
+    trace::Role::Archivist => &[],
+  };
+  let mut nodes : Vec<(String, &Loc, &Node)> =
+    dcg.table.iter().map(|(loc, nd)| (string_of_loc(loc), loc, nd)).collect();
+  nodes.sort_by(|a, b| a.0.cmp(&b.0));
+  for &(_, loc, nd) in nodes.iter() {
+    writeln!(writer, "  {} [{}];", dot_id_of_loc(loc), dot_attrs_of_node(loc, nd)).unwrap();
+  };
+  for tr in traces.iter() {
+    match tr.edge {
+      trace::EffectEdge::Fwd(trace::Edge{ loc:None, ref succ }) =>
+        if dcg.table.contains_key(&succ.loc) {
+          writeln!(writer, "  \"editor\" -> {} [{}];",
+                   dot_id_of_loc(&succ.loc), dot_attrs_of_succ(succ)).unwrap();
+        },
+      trace::EffectEdge::Fwd(_) | trace::EffectEdge::Bwd(_) => (),
+      // An effect without an edge (e.g., `Debug`)
+      trace::EffectEdge::None => (),
+    }
+  };
+  for &(_, loc, nd) in nodes.iter() {
+    match succs_of_node(nd) {
+      None => (),
+      Some(succs) => for succ in succs.iter() {
+        writeln!(writer, "  {} -> {} [{}];",
+                 dot_id_of_loc(loc), dot_id_of_loc(&succ.loc), dot_attrs_of_succ(succ)).unwrap();
+      }
+    }
+  };
+  writeln!(writer, "}}").unwrap();
+}
+
+fn write_engine_metrics_dot(path:String, graph_name:&str, role:trace::Role, metrics:&EngineMetrics) {
+  match metrics.reflect_dcg {
+    None => (),
+    Some(ref dcg) => {
+      let f = File::create(path).unwrap();
+      let mut writer = BufWriter::new(f);
+      write_dcg_dot(&mut writer, graph_name, dcg, role, &metrics.reflect_traces);
+      writer.flush().unwrap();
+    }
+  }
+}
+
+/// Writes the reflected DCGs of each sample as DOT files, one per
+/// sample and phase: `<output_dir>/<labname>/dcg/seed-<seed>-batch-<batch>-edit.dot`
+/// holds the DCG after the Editor's edit (and before the update), and
+/// `...-update.dot` holds the DCG after the Archivist's update.  Does
+/// nothing for samples without reflected DCGs (e.g., when the
+/// parameter `reflect_dcg` is false).
+pub fn write_lab_dcgs(params:&LabParams, lab:&Box<Lab>, results:&LabResults) {
+  let labname = string_of_name( &lab.name() );
+  let out = &params.output_dir;
+  fs::create_dir_all(format!("{}/{}/dcg/", out, labname)).unwrap();
+  for trajectory in results.trajectories.iter() {
+    for sample in trajectory.samples.iter() {
+      for &(phase, role, metrics) in [("edit",   trace::Role::Editor,    &sample.dcg_sample.process_input),
+                                      ("update", trace::Role::Archivist, &sample.dcg_sample.compute_output)].iter() {
+        let graph_name = format!("{}-seed-{}-batch-{}-{}", labname, trajectory.seed, sample.batch_name, phase);
+        write_engine_metrics_dot(
+          format!("{}/{}/dcg/seed-{}-batch-{}-{}.dot",
+                  out, labname, trajectory.seed, sample.batch_name, phase),
+          &graph_name, role, metrics)
+      }
+    }
+  }
+}
@@ -0,0 +1,988 @@
+extern crate time;
+
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::str::FromStr;
+use std::time::Duration;
+
+use clap;
+use adapton::reflect::string_of_name;
+use labdef::*;
+use labsweep::{SweepParams,SweepMode,SweepSummary};
+use labdata::{OutputFormat,DataRow};
+use labcompare::Tolerance;
+use labselect::LabSelection;
+use labconfig::Experiment;
+use toml::Value;
+use toml::value::Table;
+use {labviz,labdata,labdot,labrun,labproc,labscript,labsweep,labcompare,labselect,labconfig};
+
+/// Parses the output formats given on the command line, as a
+/// comma-separated list (e.g., `html,csv`).
+fn formats_of_string(s:&str) -> Option<Vec<OutputFormat>> {
+  let mut formats = vec![];
+  for format in s.split(',') {
+    match format.trim().parse::<OutputFormat>() {
+      Ok(format) => formats.push(format),
+      Err(_) => return None,
+    }
+  };
+  Some(formats)
+}
+
+/// Parses a list of numbers given on the command line (e.g., input
+/// seeds or input sizes): either a range (e.g., `0..20`, excluding
+/// 20), optionally with a step (e.g., `1000..10000..1000`), or a
+/// comma-separated list (e.g., `1,2,7`).
+fn usizes_of_string(s:&str) -> Option<Vec<usize>> {
+  let range : Vec<_> = s.split("..").map(|x| x.trim().parse::<usize>()).collect();
+  match &range[..] {
+    &[Ok(lo), Ok(hi)] if lo < hi => Some((lo..hi).collect()),
+    &[Ok(lo), Ok(hi), Ok(step)] if lo < hi && step > 0 => Some((lo..hi).step_by(step).collect()),
+    &[_, _] | &[_, _, _] => None,
+    _ => {
+      let mut xs = vec![];
+      for x in s.split(',') {
+        match x.trim().parse::<usize>() {
+          Ok(x) => xs.push(x),
+          Err(_) => return None,
+        }
+      };
+      Some(xs)
+    }
+  }
+}
+
+/// The least and the greatest value of each numeric option (e.g.,
+/// `size`), other than `seeds`.  Each counts something, of which
+/// there must be at least one, except for `warmup`; a size is at most
+/// `MAX_SIZE`.
+fn range_of_option(arg:&str) -> (usize, usize) {
+  match arg {
+    "size"   => (1, MAX_SIZE),
+    "warmup" => (0, ::std::usize::MAX),
+    _        => (1, ::std::usize::MAX),
+  }
+}
+
+/// Checks a value of the option `--<arg>` (See `range_of_option`).
+fn check_range(arg:&str, x:usize) -> Result<usize, String> {
+  let (lo, hi) = range_of_option(arg);
+  if x < lo { Err(format!("`--{}` must be at least {}, not {}.", arg, lo, x)) }
+  else if x > hi { Err(format!("`--{}` must be at most {}, not {}.", arg, hi, x)) }
+  else { Ok(x) }
+}
+
+/// Parses the values of the option `--<arg>`, a parameter that we
+/// may sweep (See `usizes_of_string`); there must be at least one,
+/// and each must be in range (See `check_range`).
+fn sweep_values_of_string(arg:&str, s:&str) -> Result<Vec<usize>, String> {
+  match usizes_of_string(s) {
+    Some(ref xs) if xs.len() > 0 => xs.iter().map(|x| check_range(arg, *x)).collect(),
+    _ => Err(format!("couldn't parse the values of `--{}`: `{}`.", arg, s)),
+  }
+}
+
+/// The values of a parameter that we may sweep (See
+/// `sweep_values_of_string`); by default, only `default`.
+fn sweep_values(args:&clap::ArgMatches, arg:&str, default:usize) -> Vec<usize> {
+  match args.value_of(arg) {
+    None => vec![default],
+    Some(s) => match sweep_values_of_string(arg, s) {
+      Ok(xs) => xs,
+      Err(e) => usage_error(e),
+    }
+  }
+}
+
+/// Parses the tolerance of `--compare`: the tolerance for time,
+/// optionally followed by the tolerance for effect counts (by default,
+/// zero), separated by a comma (e.g., `0.2,0.05`).  Each must be a
+/// finite number, at least zero.
+fn tolerance_of_string(s:&str) -> Option<Tolerance> {
+  let tols : Vec<_> = s.split(',').map(|x| match x.trim().parse::<f64>() {
+    Ok(t) if t >= 0.0 && t.is_finite() => Some(t),
+    _ => None,
+  }).collect();
+  match &tols[..] {
+    &[Some(time)] => Some(Tolerance{ time, effects:0.0 }),
+    &[Some(time), Some(effects)] => Some(Tolerance{ time, effects }),
+    _ => None,
+  }
+}
+
+/// The default lab parameters, before the command-line arguments.
+pub fn lab_params_defaults() -> LabParams {
+  
+  let do_reflect = true;
+  //let do_reflect = false;
+  
+  return LabParams {
+    sample_params: SampleParams {
+      input_seeds: vec![0],
+      generate_params: GenerateParams {
+        size: 8,
+        //size: 16,
+        //size: 32,
+        //size: 64,
+        //size: 256,
+        //size: 1024,
+        //size: 10000,
+        //size: 20000,
+        //size: 50000,
+        // size: 100000,
+        //size: 200000,
+        gauge:1,
+        nominal_strategy: NominalStrategy::Regular,
+      },
+      demand: 6,
+      validate_output: true,
+      change_batch_size: 1,
+      reflect_dcg: do_reflect,
+      reflect_trace: do_reflect,
+      timing_warmup: 0,
+      timing_reps: 1,
+      count_effects: true,
+      record_edits: false,
+    },
+    change_batch_loopc:10,
+    replay_edits: None,
+    output_dir: String::from("lab-results"),
+    overrides: ParamsOverride::default(),
+    preset: None,
+  }
+}
+
+/// Reports a minimal counterexample for a lab's from-scratch
+/// consistency failure, if any, with a command line that reproduces it.
+fn shrink_lab(params:&LabParams, lab:&Box<Lab>, results:&LabResults) {
+  match labrun::shrink_failure(lab, params, results) {
+    None => (),
+    Some(s) => {
+      let labname = string_of_name( &lab.name() );
+      let gp = &s.params.sample_params.generate_params;
+      println!("Shrunk the failure of lab `{}`, after {} runs: input seed {}, batch {}.",
+               labname, s.runs, s.seed, s.batch_name);
+      println!("Minimal params:\n\t{:?}", s.params);
+      println!("To reproduce:\n\tcargo run -- run --lab {} --seeds {} --size {} --artfreq {} --nominal {} --batch {} --loopc {} --demand {}",
+               labname, s.seed, gp.size, gp.gauge,
+               string_of_nominal_strategy(&gp.nominal_strategy),
+               s.params.sample_params.change_batch_size, s.params.change_batch_loopc,
+               s.params.sample_params.demand);
+    }
+  }
+}
+
+/// Prints each failure of a lab (See `LabResults::failures`).
+pub fn report_failures(lab:&Box<Lab>, results:&LabResults) {
+  for failure in results.failures() {
+    println!("Lab failed: {}: input seed {:?}, batch {:?}, engine {:?}: {}",
+             string_of_name( &lab.name() ), failure.seed, failure.batch_name, failure.engine, failure.message);
+  }
+}
+
+/// Runs one lab, and writes its results in each output format, along
+/// with its failures and its recorded edits (if any).  With `shrink`,
+/// also searches for a minimal counterexample to a consistency
+/// failure (See `labrun::shrink_failure`).  The lab's defaults change
+/// the parameters, except for those that the user chose (See
+/// `labdef::lab_params`).
+pub fn run_lab(params:&LabParams, formats:&Vec<OutputFormat>, shrink:bool, lab:&Box<Lab>) -> LabResults {
+  let params = &lab_params(params, lab.info());
+  println!("Running lab: {}", string_of_name( &lab.name() ) );
+  let result = lab.run(params);
+  report_failures(lab, &result);
+  labdata::write_lab_failures(params, lab, &result);
+  if params.sample_params.record_edits { labscript::write_lab_edit_scripts(params, lab, &result) };
+  if shrink { shrink_lab(params, lab, &result) };
+  for format in formats.iter() {
+    match *format {
+      OutputFormat::Html => labviz::write_lab_results(params, lab, &result),
+      OutputFormat::Dot  => labdot::write_lab_dcgs(params, lab, &result),
+      _ => labdata::write_lab_data(format, params, lab, &result),
+    }
+  };
+  result
+}
+
+/// Writes the summary of a set of labs in each output format, e.g.,
+/// the summary page for HTML (See `labviz::write_lab_results_summary`).
+pub fn write_labs_summary(params:&LabParams, formats:&Vec<OutputFormat>, labs:&Vec<Box<Lab>>, results:&Vec<LabResults>) {
+  for format in formats.iter() {
+    match *format {
+      OutputFormat::Html => labviz::write_lab_results_summary(params, labs, results),
+      OutputFormat::Dot  => (), // No summary; one graph per sample and phase
+      _ => labdata::write_lab_data_summary(format, params, labs, results),
+    }
+  }
+}
+
+/// Runs a set of labs, one after another (See `run_lab`), and writes
+/// their summary (See `write_labs_summary`).  Returns the results of
+/// each lab, in order.
+pub fn run_labs(params:&LabParams, formats:&Vec<OutputFormat>, shrink:bool, labs:&Vec<Box<Lab>>) -> Vec<LabResults> {
+  let mut results = vec![];
+  for lab in labs.iter() {
+    let result = run_lab(params, formats, shrink, lab);
+    results.push(result);
+  }  
+  write_labs_summary(params, formats, labs, &results);
+  results
+}
+
+/// Like `run_labs`, except that each lab runs in a child process (See
+/// `labproc`), given the command-line arguments of this one; hence,
+/// the program must run `main` with the same labs.  The results of
+/// each lab are those that its process wrote (See
+/// `labproc::run_lab_isolated`), from which we write the summary, as
+/// `run_labs` does.
+pub fn run_labs_isolated(params:&LabParams, formats:&Vec<OutputFormat>, labs:&Vec<Box<Lab>>,
+                         args:&Vec<String>, timeout:Option<Duration>) -> Vec<LabResults> {
+  let mut results = vec![];
+  for lab in labs.iter() {
+    let labname = string_of_name( &lab.name() );
+    println!("Running lab in a child process: {}", labname);
+    let result = labproc::run_lab_isolated(&params.output_dir, &labname, args, formats, timeout);
+    report_failures(lab, &result);
+    results.push(result);
+  }
+  write_labs_summary(params, formats, labs, &results);
+  results
+}
+
+/// The data formats among the output formats; CSV if there are none.
+fn data_formats(formats:&Vec<OutputFormat>) -> Vec<OutputFormat> {
+  let mut data_formats : Vec<_> = formats.iter()
+    .filter(|f| **f != OutputFormat::Html && **f != OutputFormat::Dot).cloned().collect();
+  if data_formats.len() == 0 { data_formats.push(OutputFormat::Csv) };
+  data_formats
+}
+
+/// Runs each lab at each point of a sweep (See `labsweep`), and writes
+/// one table, with a row per lab and point, in each data format (CSV
+/// by default), along with the samples of every point.  For HTML, we
+/// write a summary page with charts of the sweep, but not the pages
+/// of each point; we do not write DOT.  We write these files in the
+/// output directory of the points (which they share; See
+/// `labsweep::sweep_points`).  Returns the summary of each lab and
+/// point, and the samples.
+pub fn run_sweep(points:&Vec<LabParams>, formats:&Vec<OutputFormat>, labs:&Vec<Box<Lab>>) -> (Vec<SweepSummary>, Vec<DataRow>) {
+  let out = match points.first() {
+    None => return (vec![], vec![]),
+    Some(params) => params.output_dir.clone(),
+  };
+  let mut summaries = vec![];
+  let mut rows = vec![];
+  for (i, params) in points.iter().enumerate() {
+    println!("Sweep point {} of {}:\n\t{:?}", i + 1, points.len(), params);
+    for lab in labs.iter() {
+      println!("Running lab: {}", string_of_name( &lab.name() ) );
+      let params = &lab_params(params, lab.info());
+      let result = lab.run(params);
+      report_failures(lab, &result);
+      summaries.push(labsweep::summarize_sweep_point(i, params, lab, &result));
+      rows.append(&mut labdata::data_rows_of_lab_results(params, lab, &result));
+    }
+  };
+  for format in data_formats(formats).iter() {
+    labsweep::write_sweep(&out, format, &summaries);
+    labsweep::write_sweep_samples(&out, format, &rows)
+  };
+  if formats.contains(&OutputFormat::Html) {
+    labviz::write_sweep_results_summary(&out, &summaries)
+  };
+  (summaries, rows)
+}
+
+/// Reads the samples of a baseline (See `compare_to_baseline`), or
+/// exits with a usage error.
+fn read_baseline(path:&str) -> Vec<DataRow> {
+  match labdata::read_data_rows_file(path) {
+    Ok(rows) => rows,
+    Err(e) => usage_error(format!("couldn't read the baseline {}", e)),
+  }
+}
+
+/// Compares the samples of this run to a baseline (See `labcompare`),
+/// writes the comparison in each data format (CSV by default), and
+/// reports it.  Returns false if a metric regressed, or if nothing in
+/// this run matches the baseline.
+fn compare_to_baseline(out:&str, path:&str, baseline:&Vec<DataRow>, rows:&Vec<DataRow>,
+                       formats:&Vec<OutputFormat>, tolerance:&Tolerance) -> bool {
+  let comparison = labcompare::compare_data_rows(baseline, rows, tolerance);
+  for format in data_formats(formats).iter() {
+    labcompare::write_comparison(out, format, &comparison)
+  };
+  labcompare::write_comparison_report(&mut ::std::io::stdout(), &comparison, tolerance);
+  if comparison.changes.len() == 0 {
+    println!("Error: nothing in this run matches the baseline `{}`.", path);
+    false
+  } else {
+    comparison.regressions().len() == 0
+  }
+}
+
+/// The name of the subdirectory of a run, if any: its name (`--name`),
+/// after the current time (`--timestamp`), e.g., `2017-03-01-142501-big`.
+fn run_name(name:Option<&str>, timestamp:bool) -> Option<String> {
+  let time = if timestamp { Some(format!("{}", time::now().strftime("%Y-%m-%d-%H%M%S").unwrap())) } else { None };
+  match (time, name) {
+    (Some(time), Some(name)) => Some(format!("{}-{}", time, name)),
+    (Some(time), None)       => Some(time),
+    (None, Some(name))       => Some(String::from(name)),
+    (None, None)             => None,
+  }
+}
+
+/// Describes a run in its directory, for the index of runs (See
+/// `labviz::write_runs_index`): its command line (`argv`, without the
+/// program name) and its parameters.
+fn write_run_info(params:&LabParams, argv:&Vec<String>) {
+  fs::create_dir_all(&params.output_dir).unwrap();
+  let mut writer = BufWriter::new(File::create(format!("{}/{}", params.output_dir, labviz::RUN_INFO_FILE)).unwrap());
+  writeln!(writer, "command\tadapton-lab {}", argv.join(" ")).unwrap();
+  writeln!(writer, "params\t{:?}", params).unwrap();
+  writer.flush().unwrap();
+}
+
+/// The options of the subcommands that run labs (`run`, `viz` and
+/// `bench`), which are also the options of the program without a
+/// subcommand.
+const RUN_OPTIONS : &'static str = "\
+    --isolate     'run each lab in a child process, reporting crashes (e.g., stack overflows) and timeouts as lab failures; not for a sweep'
+    --record      'record the edits of each trajectory as an edit script, for replaying them exactly with --replay'
+    --shrink      'on an output validation failure, search for smaller parameters that still fail, and report them'
+    --timestamp   'write the results to a subdirectory of the output directory, named by the current time, keeping past runs (See runs.html)'
+    -L,  --lab=[labname]       'the labs to run, from the lab catalog (See the list subcommand); a list of names, globs (e.g., list-lazy-*) or regexes (e.g., /sort[12]$/), where !<pattern> excludes and tag:<tag> selects by tag; the default is all labs.'
+         --exclude=[exclude]   'the labs not to run, as a list of names, globs or regexes (See --lab).'
+         --tag=[tag]           'run only the labs with each of these tags, as a list (e.g., list,lazy; See the list subcommand).'
+    -s,  --size=[size]         'for the Editor: the initial input size generated by the Editor; several values (e.g., 1000,10000 or 1000..10000..1000) give a sweep.'
+    -a,  --artfreq=[artfreq]   'for the Editor: the frequency of articulations, measured in non-nominal constructors; several values give a sweep.'
+    -b,  --batch=[batch]       'for the Editor: the number of edits that the Editor performs at once; several values give a sweep.'
+    -l,  --loopc=[loopc]       'for the Editor and Archivist: the loop count of edit-and-compute; several values give a sweep.'
+    -d,  --demand=[demand]     'for the Archivist: the number of output elements to demand; only relevant for lazy Archivists; several values give a sweep.'
+    -n,  --nominal=[nominal]   'for the Editor: how to name articulations; either regular (by position) or bycontent (by hashing content).'
+         --warmup=[warmup]     'for timing: the number of untimed runs of the naive Archivist before timing each sample.'
+         --reps=[reps]         'for timing: the number of timed runs of each sample; the DCG engine replays each sample on reps-1 more DCGs.'
+         --seeds=[seeds]       'for the Editor: the input seeds, with one trajectory per seed; a range (e.g., 0..20) or a list (e.g., 1,2,7).'
+         --effects=[effects]   'a boolean indicating whether to count the trace effects of each sample; this traces each sample, adding overhead to DCG times.'
+         --compare=[baseline]  'compare the samples to a baseline (a samples.csv or samples.tsv of an earlier run), and exit with status 1 on a regression.'
+         --tolerance=[tolerance] 'with --compare: the relative increase that is a regression, for the DCG update time and, optionally, each effect count (e.g., 0.2 or 0.2,0.05); the defaults are 0.1 and 0.'
+         --sweep=[sweep]       'how to combine the values of swept parameters into points; either product (every combination; the default) or zip (the i-th values together).'
+         --timeout=[timeout]   'with --isolate: the number of seconds to wait for each lab before killing it; the default is no timeout.'
+         --out=[out]           'the output directory; the default is lab-results.'
+         --name=[name]         'write the results to the subdirectory <name> of the output directory (after the time, with --timestamp), keeping past runs.'
+         --format=[format]     'the output formats, as a list (e.g., html,csv); each of html, csv, tsv, json or dot; the default is html.'
+         --replay=[replay]     'replay the edits of an edit script (written by --record), instead of editing randomly; the script determines the lab and the input seed.'
+         --validate=[validate] 'a boolean indicating whether to validate the output; the default is true.'
+         --config=[config]     'run each experiment of a configuration file (e.g., a config.toml of an earlier run), with the other options, which replace those of the file.'";
+
+/// The options of `RUN_OPTIONS`: for each, its long name (e.g.,
+/// `lab`), its name in the parsed arguments (e.g., `labname`), and
+/// whether it has no value (e.g., `isolate`).
+fn run_options() -> Vec<(&'static str, &'static str, bool)> {
+  RUN_OPTIONS.lines().filter_map(|line| {
+    let usage = line.split('\'').next().unwrap();
+    usage.split_whitespace().find(|w| w.starts_with("--")).map(|w| {
+      let w = &w[2..];
+      match w.find("=[") {
+        None => (w, w, true),
+        Some(i) => (&w[..i], &w[i + 2..w.len() - 1], false),
+      }
+    })
+  }).collect()
+}
+
+/// The value of an option in a configuration, from its text (e.g.,
+/// `1000,10000`), as close to the text as we can read it back: a
+/// number, a boolean, a list of them (or of text) of one type (as
+/// TOML requires), or else text.
+fn value_of_arg(s:&str) -> Value {
+  let item = |s:&str| match (s.parse::<i64>(), s.parse::<bool>()) {
+    (Ok(i), _) if i >= 0 => Value::Integer(i),
+    (_, Ok(b)) => Value::Boolean(b),
+    _ => Value::String(String::from(s)),
+  };
+  let items : Vec<Value> = s.split(',').map(item).collect();
+  if items.len() == 1 { items.into_iter().next().unwrap() }
+  else if items.iter().all(|v| v.type_str() == items[0].type_str()) { Value::Array(items) }
+  else { Value::String(String::from(s)) }
+}
+
+/// The options of `RUN_OPTIONS` that the arguments give, by their
+/// long names, as in a configuration (See `labconfig::Experiment`),
+/// without `--config` and `--name`.
+fn options_of_args(args:&clap::ArgMatches) -> Table {
+  let mut options = Table::new();
+  for (long, name, flag) in run_options().into_iter() {
+    if long == "config" || long == "name" || !args.is_present(name) { continue };
+    let val = if flag { Value::Boolean(true) } else { value_of_arg(args.value_of(name).unwrap()) };
+    options.insert(String::from(long), val);
+  };
+  options
+}
+
+/// The effective configuration of a run (See `labconfig`): the
+/// options that it gives, and the values of the other parameters that
+/// do not depend on the lab (e.g., the input seeds).
+fn experiment_of_run(preset:&Option<Preset>, args:&clap::ArgMatches, params:&LabParams,
+                     formats:&Vec<OutputFormat>, out:&str) -> Experiment {
+  let mut options = options_of_args(args);
+  {
+    let sp = &params.sample_params;
+    let mut default = |key:&str, val:Value| { options.entry(String::from(key)).or_insert(val); };
+    default("seeds",    Value::Array(sp.input_seeds.iter().map(|s| Value::Integer(*s as i64)).collect()));
+    default("nominal",  Value::String(String::from(string_of_nominal_strategy(&sp.generate_params.nominal_strategy))));
+    default("warmup",   Value::Integer(sp.timing_warmup as i64));
+    default("reps",     Value::Integer(sp.timing_reps as i64));
+    default("effects",  Value::Boolean(sp.count_effects));
+    default("validate", Value::Boolean(sp.validate_output));
+    default("format",   Value::Array(formats.iter().map(|f| Value::String(String::from(labdata::extension_of_format(f)))).collect()));
+    default("out",      Value::String(String::from(out)));
+  };
+  Experiment{ name: args.value_of("name").map(String::from), preset: preset.clone(), options }
+}
+
+/// The exit status of `main` when a run regressed from its baseline,
+/// or matched none of it (See `--compare` and the `compare`
+/// subcommand).
+pub const EXIT_REGRESSION : i32 = 1;
+/// The exit status of `main` on an error in the command-line
+/// arguments, e.g., an option that we cannot parse, an unknown lab, or
+/// a file that we cannot read.
+pub const EXIT_USAGE : i32 = 2;
+/// The exit status of `main` when a lab failed (See
+/// `LabResults::failures`).  A mismatch of the DCG and naive outputs
+/// is not a failure; we report it in the results (and `lab_test!`
+/// checks for it).
+pub const EXIT_LAB_FAILED : i32 = 3;
+
+/// Reports an error in the command-line arguments, and exits with
+/// `EXIT_USAGE`.
+fn usage_error(msg:String) -> ! {
+  writeln!(::std::io::stderr(), "Error: {}", msg).unwrap();
+  ::std::process::exit(EXIT_USAGE)
+}
+
+/// The value of an option, if given; otherwise, `default`.  Exits with
+/// a usage error if we cannot parse the value.
+fn value_or<T:FromStr>(args:&clap::ArgMatches, arg:&str, default:T) -> T {
+  match args.value_of(arg) {
+    None => default,
+    Some(s) => match s.parse::<T>() {
+      Ok(x) => x,
+      Err(_) => usage_error(format!("couldn't parse the value of `--{}`: `{}`.", arg, s)),
+    }
+  }
+}
+
+/// The value of a numeric option, as `value_or` parses it, if it is
+/// in range (See `check_range`).
+fn number_or(args:&clap::ArgMatches, arg:&str, default:usize) -> usize {
+  match check_range(arg, value_or(args, arg, default)) {
+    Ok(x) => x,
+    Err(e) => usage_error(e),
+  }
+}
+
+/// Changes the lab parameters according to a preset, for every lab;
+/// each lab may then change them (See `LabInfo::preset`).
+pub fn apply_preset(preset:&Preset, params:&mut LabParams) {
+  params.preset = Some(preset.clone());
+  match *preset {
+    Preset::Viz => {
+      params.sample_params.generate_params.size = 8;
+      params.sample_params.reflect_dcg   = true;
+      params.sample_params.reflect_trace = true;
+    },
+    Preset::Bench => {
+      params.sample_params.generate_params.size = 100000;
+      params.sample_params.reflect_dcg   = false;
+      params.sample_params.reflect_trace = false;
+      params.sample_params.count_effects = false;
+    },
+  }
+}
+
+/// Prints each lab of a catalog: its name and its URL (if any), and
+/// then its description, its tags, whether it is lazy, its types and
+/// its defaults and presets (See `LabInfo`); with `names`, only its
+/// name.
+fn list_labs(labs:&Vec<Box<Lab>>, names:bool) {
+  for lab in labs.iter() {
+    let labname = string_of_name (&lab.name());
+    if names { println!("{}", labname); continue };
+    println!("{:32} {}", labname, match *lab.url() { Some(ref s) => s, None => "" } );
+    let info = lab.info();
+    let types = lab.types();
+    if info.description.len() > 0 { println!("    {}", info.description) };
+    println!("    tags: {}{}", info.tags.join(","), if info.lazy { "; lazy (uses --demand)" } else { "" });
+    println!("    types: {} -> {}; Editor {} (state {}); Archivist {}",
+             types.input, types.output, types.editor, types.editst, types.archivist);
+    if !info.defaults.is_empty() { println!("    defaults: {}", info.defaults) };
+    if !info.viz.is_empty()      { println!("    viz: {}", info.viz) };
+    if !info.bench.is_empty()    { println!("    bench: {}", info.bench) };
+  }
+}
+
+/// The labs that `--lab`, `--exclude` and `--tag` select (See
+/// `labselect::selection_of_lists`).
+fn selection_of_args(args:&clap::ArgMatches) -> LabSelection {
+  let list = |arg| args.value_of(arg).unwrap_or("");
+  labselect::selection_of_lists(list("labname"), list("exclude"), list("tag"))
+}
+
+/// Selects the labs of a catalog (See `labselect::select_labs`), or
+/// exits with a usage error, after listing the catalog.
+fn select_labs(catalog:fn() -> Vec<Box<Lab>>, selection:&LabSelection) -> Vec<Box<Lab>> {
+  match labselect::select_labs(catalog(), selection) {
+    Ok(labs) => labs,
+    Err(e) => {
+      println!("Hint: Here are the labs of the catalog (See the list subcommand):");
+      list_labs(&catalog(), true);
+      usage_error(e)
+    }
+  }
+}
+
+/// The subcommands (See `subcommand`).
+const SUBCOMMANDS : [&'static str; 5] = ["list", "run", "viz", "bench", "compare"];
+
+/// The command-line interface of a subcommand (See `SUBCOMMANDS`).
+fn subcommand(name:&'static str) -> clap::App<'static, 'static, 'static, 'static, 'static, 'static> {
+  let run_command = |about| clap::SubCommand::with_name(name).about(about).args_from_usage(RUN_OPTIONS);
+  match name {
+    "list"  => clap::SubCommand::with_name(name)
+      .about("list the lab catalog: the name, URL, description, tags, types and defaults of each lab")
+      .args_from_usage("\
+    --names       'list only the name of each lab, one per line, e.g., for scripts'
+    -L,  --lab=[labname]       'list only these labs, as a list of names, globs (e.g., list-lazy-*) or regexes (e.g., /sort[12]$/), where !<pattern> excludes and tag:<tag> selects by tag.'
+         --exclude=[exclude]   'do not list these labs, as a list of names, globs or regexes.'
+         --tag=[tag]           'list only the labs with each of these tags, as a list (e.g., list,lazy).'"),
+    "run"   => run_command("run the labs (by default, the whole catalog)"),
+    "viz"   => run_command("run the labs for visualization: small input size, with reflection"),
+    "bench" => run_command("run the labs for benchmarking: large input size, without reflection"),
+    "compare" => clap::SubCommand::with_name(name)
+      .about("compare the samples of two runs, and exit with status 1 on a regression")
+      .args_from_usage("\
+    <baseline>                 'the samples of the earlier run (a samples.csv or samples.tsv)'
+    <current>                  'the samples of the later run, with the same format'
+         --tolerance=[tolerance] 'the relative increase that is a regression, for the DCG update time and, optionally, each effect count (e.g., 0.2 or 0.2,0.05); the defaults are 0.1 and 0.'
+         --out=[out]           'the output directory, for the comparison (regressions.csv); the default is lab-results.'
+         --format=[format]     'the data formats of the comparison, as a list; each of csv, tsv or json; the default is csv.'"),
+    _ => unreachable!(),
+  }
+}
+
+/// The command-line interface: the subcommands, and the options of the
+/// program without a subcommand (See `run_command`).
+fn app() -> clap::App<'static, 'static, 'static, 'static, 'static, 'static> {
+  let app = clap::App::new("adapton-lab")
+    .version("0.0.1")
+    .author("Matthew A. Hammer <matthew.hammer@colorado.edu> and the Adapton Team")      
+    .about("Adapton Lab: Systematic laboratory environment for Adapton in Rust.
+
+    Permits the command-line experimenter to define visualizations and
+    performance evaluations of Adapton, including its standard
+    library, and (eventually) other applications that use it.
+
+    For developers, Adapton Lab consists of a generic lab definition
+    (the `Lab` trait), and a parameterized structure (the `LabDef`
+    struct) that uses several abstract parameters that define roles
+    for the 'Editor' and the 'Archivist'.
+
+    For both developers and experimenters, Adapton Lab includes a
+    catalog of instances of these abstract definitions.
+
+    Without a subcommand, Adapton Lab runs the labs, as the run
+    subcommand does.  It exits with status 1 on a regression (See
+    --compare), 2 on an error in its arguments, and 3 if a lab failed. ")
+    .args_from_usage(RUN_OPTIONS)
+    .args_from_usage("\
+    --run-viz     'like the viz subcommand: run lab catalog for visualization: small input size, with reflection'
+    --run-bench   'like the bench subcommand: run lab catalog for benchmarking: large input size, without reflection'");
+  SUBCOMMANDS.iter().fold(app, |app, name| app.subcommand(subcommand(name)))
+}
+
+/// The tolerance of `--tolerance`, or the default.
+fn tolerance_of_args(args:&clap::ArgMatches) -> Tolerance {
+  match args.value_of("tolerance") {
+    None => Tolerance{ time:0.1, effects:0.0 },
+    Some(s) => match tolerance_of_string(s) {
+      Some(t) => t,
+      None => usage_error(format!("couldn't parse the tolerance `{}`.", s)),
+    }
+  }
+}
+
+/// The output formats of `--format`, or `default`.
+fn formats_of_args(args:&clap::ArgMatches, default:OutputFormat) -> Vec<OutputFormat> {
+  match args.value_of("format") {
+    None => vec![default],
+    Some(s) => match formats_of_string(s) {
+      Some(formats) => formats,
+      None => usage_error(format!("couldn't parse the output formats `{}`.", s)),
+    }
+  }
+}
+
+/// Compares the samples of two earlier runs, as the `compare`
+/// subcommand directs.  Returns the exit status.
+fn compare_command(args:&clap::ArgMatches) -> i32 {
+  let baseline_path = args.value_of("baseline").unwrap();
+  let current_path = args.value_of("current").unwrap();
+  let baseline = read_baseline(baseline_path);
+  let current = match labdata::read_data_rows_file(current_path) {
+    Ok(rows) => rows,
+    Err(e) => usage_error(format!("couldn't read the samples {}", e)),
+  };
+  let formats = formats_of_args(args, OutputFormat::Csv);
+  let out = value_or(args, "out", String::from("lab-results"));
+  if compare_to_baseline(&out, baseline_path, &baseline, &current, &formats, &tolerance_of_args(args)) { 0 }
+  else { EXIT_REGRESSION }
+}
+
+/// Runs the experiments of a configuration file (See `labconfig`), in
+/// order, each with its preset, as its options and the other options
+/// (`args`) direct, which replace its own.  Returns the exit status:
+/// a regression in any experiment, or else a failure of a lab in any.
+fn run_config(catalog:fn() -> Vec<Box<Lab>>, preset:Option<Preset>, path:&str, args:&clap::ArgMatches) -> i32 {
+  let experiments = match labconfig::read_config_file(path) {
+    Ok(experiments) => experiments,
+    Err(e) => usage_error(format!("couldn't read the configuration {}", e)),
+  };
+  if experiments.len() > 1 && args.is_present("name") {
+    usage_error(String::from("--name applies only to a configuration with one experiment; give each experiment its name."))
+  };
+  let given = options_of_args(args);
+  let known : Vec<(&str, bool)> = run_options().into_iter().map(|o| (o.0, o.2)).collect();
+  // Parse the arguments of every experiment before running any
+  let runs : Vec<(Option<Preset>, Vec<String>, clap::ArgMatches)> = experiments.iter().map(|experiment| {
+    let preset = preset.clone().or(experiment.preset.clone());
+    let command = match preset { Some(ref p) => string_of_preset(p), None => "run" };
+    let name = args.value_of("name").map(String::from).or(experiment.name.clone());
+    let context = |e:String| match name {
+      None => format!("in {}: {}", path, e),
+      Some(ref name) => format!("in the experiment `{}` of {}: {}", name, path, e),
+    };
+    let mut options = experiment.options.clone();
+    options.extend(given.clone().into_iter());
+    let mut argv = vec![String::from(command)];
+    match labconfig::args_of_options(&options, &known) {
+      Ok(mut a) => argv.append(&mut a),
+      Err(e) => usage_error(context(e)),
+    };
+    match name { Some(ref name) => { argv.push(String::from("--name")); argv.push(name.clone()) }, None => () };
+    match subcommand(command).bin_name(&format!("adapton-lab {}", command)).get_matches_from_safe(argv.iter()) {
+      Ok(matches) => (preset, argv, matches),
+      Err(e) => usage_error(context(e.error)),
+    }
+  }).collect();
+  let statuses : Vec<i32> = runs.iter().map(|&(ref preset, ref argv, ref matches)| {
+    println!("Running the experiment: adapton-lab {}", argv.join(" "));
+    run_command(catalog, preset.clone(), matches, argv)
+  }).collect();
+  if statuses.contains(&EXIT_REGRESSION) { EXIT_REGRESSION }
+  else if statuses.contains(&EXIT_LAB_FAILED) { EXIT_LAB_FAILED }
+  else { 0 }
+}
+
+/// Runs the labs of a catalog, after a preset (if any), as the options
+/// direct (See `RUN_OPTIONS`); `argv` are the arguments, without the
+/// program name, e.g., for the child processes of `--isolate`.
+/// Returns the exit status.
+fn run_command(catalog:fn() -> Vec<Box<Lab>>, preset:Option<Preset>, args:&clap::ArgMatches, argv:&Vec<String>) -> i32 {
+  match args.value_of("config") {
+    None => (),
+    Some(path) => return run_config(catalog, preset, path, args),
+  };
+  // Start with the defaults, changing them according to the preset and the options:
+  let mut params = lab_params_defaults();
+  match preset { Some(ref preset) => apply_preset(preset, &mut params), None => () };
+
+  // Each of these parameters may have several values, for a sweep
+  let sweep = SweepParams{
+    sizes:       sweep_values(args, "size",    params.sample_params.generate_params.size),
+    gauges:      sweep_values(args, "artfreq", params.sample_params.generate_params.gauge),
+    batch_sizes: sweep_values(args, "batch",   params.sample_params.change_batch_size),
+    demands:     sweep_values(args, "demand",  params.sample_params.demand),
+    loopcs:      sweep_values(args, "loopc",   params.change_batch_loopc),
+  };
+  params.sample_params.generate_params.size  = sweep.sizes[0];
+  params.sample_params.generate_params.gauge = sweep.gauges[0];
+  params.sample_params.change_batch_size     = sweep.batch_sizes[0];
+  params.sample_params.demand                = sweep.demands[0];
+  params.change_batch_loopc                  = sweep.loopcs[0];
+  // The values that the user chose, which the defaults of a lab do not replace
+  let chosen = |arg, x:usize| if args.is_present(arg) { Some(x) } else { None };
+  params.overrides = ParamsOverride{
+    size:               chosen("size",    sweep.sizes[0]),
+    gauge:              chosen("artfreq", sweep.gauges[0]),
+    change_batch_size:  chosen("batch",   sweep.batch_sizes[0]),
+    demand:             chosen("demand",  sweep.demands[0]),
+    change_batch_loopc: chosen("loopc",   sweep.loopcs[0]),
+  };
+  match args.value_of("seeds") {
+    None => (),
+    Some(s) => match usizes_of_string(s) {
+      Some(ref seeds) if seeds.len() > 0 => params.sample_params.input_seeds = seeds.clone(),
+      _ => usage_error(format!("couldn't parse the input seeds `{}`.", s)),
+    }
+  };
+  {
+    let n = params.sample_params.generate_params.nominal_strategy.clone() ;
+    params.sample_params.generate_params.nominal_strategy = value_or(args, "nominal", n);
+  };
+  {
+    let w = params.sample_params.timing_warmup ;
+    params.sample_params.timing_warmup = number_or(args, "warmup", w);
+  };
+  {
+    let r = params.sample_params.timing_reps ;
+    params.sample_params.timing_reps = number_or(args, "reps", r);
+  };
+  {
+    let c = params.sample_params.count_effects ;
+    params.sample_params.count_effects = value_or(args, "effects", c);
+  };
+  {
+    let v = params.sample_params.validate_output ;
+    params.sample_params.validate_output = value_or(args, "validate", v);
+  };
+  let formats = formats_of_args(args, OutputFormat::Html);
+
+  let out = value_or(args, "out", params.output_dir.clone());
+  let run = run_name(args.value_of("name"), args.is_present("timestamp"));
+  params.output_dir = match run {
+    None => out.clone(),
+    Some(ref run) => format!("{}/{}", out, run),
+  };
+
+  let shrink = args.is_present("shrink");
+  params.sample_params.record_edits = args.is_present("record");
+  params.replay_edits = match args.value_of("replay") {
+    None => None,
+    Some(path) => match labscript::read_edit_script_file(path) {
+      Ok(script) => Some(script),
+      Err(e) => usage_error(format!("couldn't read the edit script {}", e)),
+    }
+  };
+
+  let sweep_mode = match args.value_of("sweep") {
+    None => None,
+    Some(s) => match s.parse::<SweepMode>() {
+      Ok(mode) => Some(mode),
+      Err(e) => usage_error(e),
+    }
+  };
+  let sweep_points = 
+    if sweep.is_sweep() || sweep_mode.is_some() {
+      match labsweep::sweep_points(&params, &sweep, &sweep_mode.unwrap_or(SweepMode::Product)) {
+        Ok(points) => Some(points),
+        Err(e) => usage_error(e),
+      }
+    } else { None };
+
+  let baseline_path = args.value_of("baseline");
+  let baseline = baseline_path.map(read_baseline);
+  if baseline.is_none() && args.is_present("tolerance") {
+    usage_error(String::from("--tolerance applies only with --compare."))
+  };
+  let tolerance = tolerance_of_args(args);
+  let isolate = args.is_present("isolate");
+  if !isolate && args.is_present("timeout") {
+    usage_error(String::from("--timeout applies only with --isolate."))
+  };
+  if isolate && sweep_points.is_some() {
+    usage_error(String::from("--isolate does not apply to a sweep, which runs its points in this process; run each point on its own instead."))
+  };
+  let timeout : Option<u64> = match args.value_of("timeout") { None => None, Some(_) => Some(number_or(args, "timeout", 0) as u64) };
+
+  // Select the labs; an edit script determines its lab
+  let selection = selection_of_args(args);
+  let mut labs = select_labs(catalog, &selection);
+  match params.replay_edits {
+    None => (),
+    Some(ref script) => {
+      if !labs.iter().any(|lab| string_of_name( &lab.name() ) == script.labname) {
+        usage_error(format!("the edit script is for the lab `{}`, which the selection excludes.", script.labname))
+      };
+      labs.retain(|lab| string_of_name( &lab.name() ) == script.labname)
+    }
+  };
+  let one_lab = labs.len() == 1 && !(selection.is_all() && params.replay_edits.is_none());
+
+  if run.is_some() { write_run_info(&params, argv) };
+  let experiment = experiment_of_run(&preset, args, &params, &formats, &out);
+  labconfig::write_config(&params.output_dir, &experiment);
+  println!("Lab params:\n\t{:?}", params );
+  let (rows, failed) = if let Some(ref points) = sweep_points {
+    let (summaries, rows) = run_sweep(points, &formats, &labs);
+    (rows, summaries.iter().any(|s| s.failed))
+  } else {
+    // Even one lab runs in a child process with `--isolate`; the child
+    // runs it alone, without `--isolate` (See `labproc::child_args`).
+    // Otherwise, a lab that we select by name runs alone, without a summary.
+    let results = if isolate {
+                    let results = run_labs_isolated(&params, &formats, &labs, argv, timeout.map(Duration::from_secs));
+                    // Each child wrote the configuration of its lab, in the same directory
+                    labconfig::write_config(&params.output_dir, &experiment);
+                    results
+                  }
+                  else if one_lab { vec![run_lab(&params, &formats, shrink, &labs[0])] }
+                  else { run_labs(&params, &formats, shrink, &labs) };
+    (labs.iter().zip(results.iter()).flat_map(
+      |(lab, result)| labdata::data_rows_of_lab_results(&lab_params(&params, lab.info()), lab, result)
+    ).collect(), results.iter().any(|r| r.failures().len() > 0))
+  };
+  let passed = match baseline {
+    None => true,
+    Some(ref baseline) => compare_to_baseline(&params.output_dir, baseline_path.unwrap(), baseline, &rows, &formats, &tolerance),
+  };
+  if run.is_some() {
+    labviz::write_runs_index(&out);
+    println!("Wrote the results to {}; see also {}/runs.html", params.output_dir, out);
+  };
+  if !passed { EXIT_REGRESSION }
+  else if failed { EXIT_LAB_FAILED }
+  else { 0 }
+}
+
+/// Runs the labs of a catalog as the command-line arguments direct
+/// (See `main`).  Returns the exit status.
+fn main2(catalog:fn() -> Vec<Box<Lab>>) -> i32 { 
+  // We parse the arguments of a subcommand on their own: On an error
+  // in them, clap would exit with status 1, which means a regression.
+  let argv : Vec<String> = env::args().collect();
+  let parsed = match argv.get(1).and_then(|cmd| SUBCOMMANDS.iter().find(|name| *name == cmd)) {
+    None => app().get_matches_from_safe(argv.iter()).map(|args| ("", args)),
+    Some(name) => subcommand(name).bin_name(&format!("adapton-lab {}", name))
+      .get_matches_from_safe(argv[1..].iter()).map(|args| (*name, args)),
+  };
+  let (name, args) = match parsed {
+    Ok(parsed) => parsed,
+    Err(e) => {
+      writeln!(::std::io::stderr(), "{}", e).unwrap();
+      return EXIT_USAGE
+    }
+  };
+  match name {
+    "list"    => { list_labs(&select_labs(catalog, &selection_of_args(&args)), args.is_present("names")); 0 },
+    "run"     => run_command(catalog, None, &args, &argv[1..].to_vec()),
+    "viz"     => run_command(catalog, Some(Preset::Viz), &args, &argv[1..].to_vec()),
+    "bench"   => run_command(catalog, Some(Preset::Bench), &args, &argv[1..].to_vec()),
+    "compare" => compare_command(&args),
+    _ => {
+      match args.subcommand_name() {
+        None => (),
+        Some(name) => usage_error(format!("give the subcommand `{}` before its options.", name)),
+      };
+      let preset = match (args.is_present("run-viz"), args.is_present("run-bench")) {
+        (true, true)   => usage_error(String::from("give only _one_ of --run-viz or --run-bench, not both.")),
+        (true, false)  => Some(Preset::Viz),
+        (false, true)  => Some(Preset::Bench),
+        (false, false) => None,
+      };
+      run_command(catalog, preset, &args, &argv[1..].to_vec())
+    }
+  }
+}
+
+/// The command-line driver of Adapton Lab, for the labs of a catalog,
+/// e.g., from the `main` of a crate with its own labs (See `labdef!`):
+///
+/// ```text
+/// fn my_labs() -> Vec<Box<Lab>> { vec![ labdef!(...), ... ] }
+/// fn main() { adapton_lab::labmain::main(my_labs) }
+/// ```
+///
+/// We run the labs in a thread with a large stack, hence we take a
+/// function that builds the catalog, rather than the catalog itself.
+/// Exits the process with `EXIT_REGRESSION` if the labs regressed
+/// from a baseline, `EXIT_USAGE` on an error in the arguments,
+/// `EXIT_LAB_FAILED` if a lab failed, and 101 on another error (e.g.,
+/// an output file that we cannot write).
+pub fn main(catalog:fn() -> Vec<Box<Lab>>) {
+  use std::thread;
+  let child =
+    thread::Builder::new().stack_size(64 * 1024 * 1024).spawn(move || { 
+      main2(catalog)
+    });
+  // Exit with an error if main2 panicked, e.g., for the parent of an
+  // isolated lab (See `labproc`), or as it directs.
+  match child.unwrap().join() {
+    Ok(0)     => (),
+    Ok(code)  => ::std::process::exit(code),
+    Err(_)    => ::std::process::exit(101),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn usizes_of_lists_and_ranges() {
+    assert_eq!(usizes_of_string("7"), Some(vec![7]));
+    assert_eq!(usizes_of_string("1, 2,7"), Some(vec![1, 2, 7]));
+    assert_eq!(usizes_of_string("0..3"), Some(vec![0, 1, 2]));
+    assert_eq!(usizes_of_string("0..10..4"), Some(vec![0, 4, 8]));
+  }
+
+  #[test]
+  fn usizes_of_malformed_strings() {
+    for s in ["", "x", "1,x", "1,,2", "1,", ",1", "-1", "1.5", "1;2"].iter() {
+      assert_eq!(usizes_of_string(s), None, "{:?}", s)
+    };
+    // Ranges that are empty, backwards, stepless, or have too many or missing bounds
+    for s in ["3..3", "5..2", "0..10..0", "0..", "..5", "0..x", "1..2..3..4", "0..10,12"].iter() {
+      assert_eq!(usizes_of_string(s), None, "{:?}", s)
+    };
+  }
+
+  #[test]
+  fn sweep_values_errors() {
+    assert_eq!(sweep_values_of_string("size", "10,100"), Ok(vec![10, 100]));
+    assert_eq!(sweep_values_of_string("size", "10,,100"),
+               Err(String::from("couldn't parse the values of `--size`: `10,,100`.")));
+    assert_eq!(sweep_values_of_string("batch", ""),
+               Err(String::from("couldn't parse the values of `--batch`: ``.")));
+    assert!(sweep_values_of_string("loopc", "4..2").is_err());
+  }
+
+  #[test]
+  fn ranges() {
+    assert_eq!(check_range("size", 1), Ok(1));
+    assert_eq!(check_range("size", 0), Err(String::from("`--size` must be at least 1, not 0.")));
+    assert_eq!(check_range("size", MAX_SIZE), Ok(MAX_SIZE));
+    assert_eq!(check_range("size", MAX_SIZE + 1), Err(format!("`--size` must be at most {}, not {}.", MAX_SIZE, MAX_SIZE + 1)));
+    for arg in ["artfreq", "batch", "demand", "loopc", "reps", "timeout"].iter() {
+      assert_eq!(check_range(arg, 0), Err(format!("`--{}` must be at least 1, not 0.", arg)));
+      assert_eq!(check_range(arg, 1), Ok(1));
+    };
+    assert_eq!(check_range("warmup", 0), Ok(0));
+  }
+
+  #[test]
+  fn sweep_values_out_of_range() {
+    assert_eq!(sweep_values_of_string("size", "0"), Err(String::from("`--size` must be at least 1, not 0.")));
+    assert_eq!(sweep_values_of_string("artfreq", "2,0"), Err(String::from("`--artfreq` must be at least 1, not 0.")));
+    assert_eq!(sweep_values_of_string("batch", "0..3"), Err(String::from("`--batch` must be at least 1, not 0.")));
+    assert_eq!(sweep_values_of_string("loopc", "1..3"), Ok(vec![1, 2]));
+    assert!(sweep_values_of_string("size", &format!("{}", MAX_SIZE + 1)).is_err());
+  }
+
+  #[test]
+  fn tolerances() {
+    let tol = |s:&str| tolerance_of_string(s).map(|t| (t.time, t.effects));
+    assert_eq!(tol("0.2"), Some((0.2, 0.0)));
+    assert_eq!(tol("0.2, 0.05"), Some((0.2, 0.05)));
+    assert_eq!(tol("0"), Some((0.0, 0.0)));
+    for s in ["", "x", "-0.1", "0.1,-1", "nan", "inf", "0.1,", ",0.1", "0.1,0.2,0.3", "0.1;0.2"].iter() {
+      assert_eq!(tol(s), None, "{:?}", s)
+    };
+  }
+}
@@ -0,0 +1,112 @@
+use std::io::prelude::*;
+
+use labviz::escape_html;
+
+/// One line of a chart: its label (for the legend), its color, and
+/// its points, in order.
+#[derive(Clone,Debug)]
+pub struct Series {
+  pub label:  String,
+  pub color:  &'static str,
+  pub points: Vec<(f64, f64)>,
+}
+
+/// A line chart, which we write as an inline SVG element (See
+/// `write_svg_chart`).
+#[derive(Clone,Debug)]
+pub struct Chart {
+  pub title:   String,
+  pub x_label: String,
+  pub y_label: String,
+  /// Place the x values on a logarithmic scale, e.g., for input sizes
+  /// that grow geometrically; all x values must be positive.
+  pub log_x:   bool,
+  /// A dashed horizontal line at this y value, e.g., a speedup of 1.
+  pub hline:   Option<f64>,
+  pub series:  Vec<Series>,
+}
+
+/// The colors of the Naive and DCG engines, in every chart.
+pub const NAIVE_COLOR : &'static str = "#d62728";
+pub const DCG_COLOR   : &'static str = "#1f77b4";
+
+/// A palette for other series (e.g., one per parameter point).
+pub const PALETTE : [&'static str; 6] = ["#1f77b4", "#ff7f0e", "#2ca02c", "#9467bd", "#8c564b", "#e377c2"];
+
+const WIDTH  : f64 = 360.0;
+const HEIGHT : f64 = 200.0;
+const LEFT   : f64 = 56.0;
+const RIGHT  : f64 = 12.0;
+const TOP    : f64 = 24.0;
+const BOTTOM : f64 = 34.0;
+
+/// A short label for a tick value.
+fn string_of_tick(v:f64) -> String {
+  let a = v.abs();
+  if a >= 100.0 || v == v.trunc() { format!("{:.0}", v) }
+  else if a >= 1.0 { format!("{:.1}", v) }
+  else { format!("{:.3}", v) }
+}
+
+/// Writes a chart as an inline SVG element, with no external
+/// dependencies (e.g., no scripts or stylesheets).  The y axis starts
+/// at zero.  A chart without points says so.
+pub fn write_svg_chart<W:Write>(writer:&mut W, chart:&Chart) {
+  let points : Vec<&(f64, f64)> = chart.series.iter().flat_map(|s| s.points.iter()).collect();
+  let xscale = |x:f64| if chart.log_x { x.ln() } else { x };
+  writeln!(writer, "<svg class=\"chart\" xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">",
+           WIDTH, HEIGHT, WIDTH, HEIGHT).unwrap();
+  writeln!(writer, "<text x=\"{}\" y=\"14\" font-size=\"12\" font-family=\"sans-serif\" text-anchor=\"middle\">{}</text>",
+           WIDTH / 2.0, escape_html(&chart.title)).unwrap();
+  if points.len() == 0 {
+    writeln!(writer, "<text x=\"{}\" y=\"{}\" font-size=\"11\" font-family=\"sans-serif\" text-anchor=\"middle\" fill=\"grey\">(no data)</text>",
+             WIDTH / 2.0, HEIGHT / 2.0).unwrap();
+    writeln!(writer, "</svg>").unwrap();
+    return
+  };
+  let (x_lo, x_hi) = points.iter().fold((::std::f64::INFINITY, ::std::f64::NEG_INFINITY),
+                                        |(lo, hi), &&(x, _)| (lo.min(x), hi.max(x)));
+  let y_hi = points.iter().fold(chart.hline.unwrap_or(0.0), |hi, &&(_, y)| hi.max(y));
+  let y_hi = if y_hi > 0.0 { y_hi * 1.05 } else { 1.0 };
+  let (sx_lo, sx_hi) = (xscale(x_lo), xscale(x_hi));
+  // Place a single x value in the middle
+  let px = |x:f64| if sx_hi > sx_lo { LEFT + (xscale(x) - sx_lo) / (sx_hi - sx_lo) * (WIDTH - LEFT - RIGHT) }
+                   else { (LEFT + WIDTH - RIGHT) / 2.0 };
+  let py = |y:f64| HEIGHT - BOTTOM - y / y_hi * (HEIGHT - TOP - BOTTOM);
+  // Axes, with ticks at the ends (and the middle) of each range
+  writeln!(writer, "<path d=\"M{} {} V{} H{}\" stroke=\"black\" fill=\"none\"/>",
+           LEFT, TOP, HEIGHT - BOTTOM, WIDTH - RIGHT).unwrap();
+  for &y in [0.0, y_hi / 2.0, y_hi].iter() {
+    writeln!(writer, "<text x=\"{}\" y=\"{}\" font-size=\"10\" font-family=\"sans-serif\" text-anchor=\"end\">{}</text>",
+             LEFT - 4.0, py(y) + 3.0, string_of_tick(y)).unwrap();
+  };
+  let x_ticks = if x_hi > x_lo { vec![x_lo, x_hi] } else { vec![x_lo] };
+  for &x in x_ticks.iter() {
+    writeln!(writer, "<text x=\"{}\" y=\"{}\" font-size=\"10\" font-family=\"sans-serif\" text-anchor=\"middle\">{}</text>",
+             px(x), HEIGHT - BOTTOM + 12.0, string_of_tick(x)).unwrap();
+  };
+  writeln!(writer, "<text x=\"{}\" y=\"{}\" font-size=\"10\" font-family=\"sans-serif\" text-anchor=\"middle\">{}{}</text>",
+           (LEFT + WIDTH - RIGHT) / 2.0, HEIGHT - 6.0, escape_html(&chart.x_label),
+           if chart.log_x { " (log scale)" } else { "" }).unwrap();
+  writeln!(writer, "<text x=\"12\" y=\"{}\" font-size=\"10\" font-family=\"sans-serif\" text-anchor=\"middle\" transform=\"rotate(-90 12 {})\">{}</text>",
+           (TOP + HEIGHT - BOTTOM) / 2.0, (TOP + HEIGHT - BOTTOM) / 2.0, escape_html(&chart.y_label)).unwrap();
+  match chart.hline {
+    None => (),
+    Some(y) => writeln!(writer, "<path d=\"M{} {:.1} H{}\" stroke=\"grey\" stroke-dasharray=\"4 3\" fill=\"none\"/>",
+                        LEFT, py(y), WIDTH - RIGHT).unwrap(),
+  };
+  // One line per series, with a dot per point, and a legend entry
+  for (i, series) in chart.series.iter().enumerate() {
+    let path : Vec<String> = series.points.iter().enumerate().map(
+      |(j, &(x, y))| format!("{}{:.1} {:.1}", if j == 0 { "M" } else { "L" }, px(x), py(y))
+    ).collect();
+    writeln!(writer, "<path d=\"{}\" stroke=\"{}\" stroke-width=\"1.5\" fill=\"none\"/>", path.join(" "), series.color).unwrap();
+    for &(x, y) in series.points.iter() {
+      writeln!(writer, "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"2\" fill=\"{}\"><title>{}: ({}, {})</title></circle>",
+               px(x), py(y), series.color, escape_html(&series.label), string_of_tick(x), string_of_tick(y)).unwrap();
+    };
+    writeln!(writer, "<text x=\"{}\" y=\"{}\" font-size=\"10\" font-family=\"sans-serif\" fill=\"{}\">{}</text>",
+             LEFT + 6.0, TOP + 10.0 + 11.0 * (i as f64), series.color, escape_html(&series.label)).unwrap();
+  };
+  writeln!(writer, "</svg>").unwrap();
+}
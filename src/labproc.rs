@@ -0,0 +1,171 @@
+use std::env;
+use std::fs;
+use std::process::{Command, ExitStatus};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use labdata::{OutputFormat, read_lab_failures, read_lab_trajectories, lab_failures_path, extension_of_format};
+use labdef::{LabResults, LabFailure, Trajectory};
+use labmain::EXIT_LAB_FAILED;
+
+/// The options of the parent that its children do not get (See
+/// `child_args`), each with a value; the parent compares all labs to
+/// a baseline, not each child, and chooses the output directory and
+/// the formats.
+const PARENT_OPTIONS : [&'static str; 9] =
+  ["--timeout", "--lab", "--exclude", "--tag", "--compare", "--tolerance", "--out", "--name", "--format"];
+
+/// The command-line arguments of a child process that runs one lab:
+/// the arguments of the parent (without the program name), without
+/// the options `--isolate` and `--timestamp`, and the options of
+/// `PARENT_OPTIONS` (e.g., `--timeout` and `--lab`), followed by
+/// `--out <out>` (the output directory of the parent's run), `--lab
+/// <labname>` and `--format` with the parent's formats and CSV, from
+/// which the parent reads the samples back (See `run_lab_isolated`).
+pub fn child_args(args:&Vec<String>, out:&str, labname:&str, formats:&Vec<OutputFormat>) -> Vec<String> {
+  let mut child = vec![];
+  let mut skip_value = false;
+  for arg in args.iter() {
+    if skip_value { skip_value = false; continue };
+    if arg == "--isolate" || arg == "--timestamp" { continue };
+    if arg == "-L" || PARENT_OPTIONS.contains(&arg.as_str()) { skip_value = true; continue };
+    if arg.starts_with("-L") || PARENT_OPTIONS.iter().any(|o| arg.starts_with(&format!("{}=", o))) { continue };
+    child.push(arg.clone())
+  };
+  child.push(String::from("--out"));
+  child.push(String::from(out));
+  child.push(String::from("--lab"));
+  child.push(String::from(labname));
+  let mut exts : Vec<_> = formats.iter().map(extension_of_format).collect();
+  if !formats.contains(&OutputFormat::Csv) { exts.push(extension_of_format(&OutputFormat::Csv)) };
+  child.push(String::from("--format"));
+  child.push(exts.join(","));
+  child
+}
+
+#[cfg(unix)]
+fn signal_of_status(status:&ExitStatus) -> Option<i32> {
+  use std::os::unix::process::ExitStatusExt;
+  status.signal()
+}
+
+#[cfg(not(unix))]
+fn signal_of_status(_status:&ExitStatus) -> Option<i32> { None }
+
+fn string_of_signal(signal:i32) -> String {
+  match signal {
+    4  => String::from("SIGILL"),
+    6  => String::from("SIGABRT; e.g., a stack overflow"),
+    7  => String::from("SIGBUS"),
+    8  => String::from("SIGFPE"),
+    9  => String::from("SIGKILL"),
+    11 => String::from("SIGSEGV; e.g., a stack overflow"),
+    _  => format!("signal {}", signal),
+  }
+}
+
+/// Describes how a child process ended, if it did not exit successfully.
+pub fn string_of_exit_status(status:&ExitStatus) -> String {
+  match (status.code(), signal_of_status(status)) {
+    (Some(code), _) => format!("the lab's process exited with code {}", code),
+    (None, Some(signal)) => format!("the lab's process was killed by {}", string_of_signal(signal)),
+    (None, None) => String::from("the lab's process ended abnormally"),
+  }
+}
+
+fn lab_failure(message:String) -> LabFailure {
+  LabFailure{ message, engine:None, seed:None, batch_name:None }
+}
+
+/// Runs one lab in a child process of this program (See
+/// `child_args`), which writes the lab's results as usual, under
+/// `<out>/<labname>/`.  Waits for the child at most `timeout`,
+/// if any, and then kills it.  Unlike a panic, neither a stack
+/// overflow nor a non-terminating lab can be caught in-process; here,
+/// we report each as a failure of the lab as a whole.  Otherwise, the
+/// results consist of the samples that the child wrote (See
+/// `labdata::read_lab_trajectories`), and the failures that it
+/// reports (See `labdata::write_lab_failures`), each in the
+/// trajectory of its seed.
+pub fn run_lab_isolated(out:&str, labname:&str, args:&Vec<String>, formats:&Vec<OutputFormat>,
+                        timeout:Option<Duration>) -> LabResults {
+  // Do not mistake the failures of a previous run for this one's
+  let _ = fs::remove_file(lab_failures_path(out, labname));
+  let exe = env::current_exe().unwrap();
+  let mut child = Command::new(exe).args(&child_args(args, out, labname, formats)).spawn().unwrap();
+  let start = Instant::now();
+  let status = loop {
+    match child.try_wait().unwrap() {
+      Some(status) => break status,
+      None => match timeout {
+        Some(t) if start.elapsed() >= t => {
+          let _ = child.kill();
+          let _ = child.wait();
+          return LabResults{
+            trajectories: vec![],
+            failure: Some(lab_failure(format!("the lab timed out after {} seconds", t.as_secs()))),
+          }
+        },
+        _ => thread::sleep(Duration::from_millis(50)),
+      }
+    }
+  };
+  let mut results = LabResults{ trajectories: vec![], failure: None };
+  // A child whose lab failed in-process reports the failures itself
+  if !status.success() && status.code() != Some(EXIT_LAB_FAILED) {
+    results.failure = Some(lab_failure(string_of_exit_status(&status)))
+  };
+  match read_lab_trajectories(out, labname) {
+    Ok(trajectories) => results.trajectories = trajectories,
+    Err(e) => if results.failure.is_none() {
+      results.failure = Some(lab_failure(format!("couldn't read the samples of the lab's process: {}", e)))
+    },
+  };
+  for failure in read_lab_failures(out, labname) {
+    match failure.seed {
+      Some(seed) => match results.trajectories.iter().position(|t| t.seed == seed) {
+        Some(i) => results.trajectories[i].failure = Some(failure),
+        None => results.trajectories.push(Trajectory{ seed, samples: vec![], failure: Some(failure), edit_script: None }),
+      },
+      None => if results.failure.is_none() { results.failure = Some(failure) },
+    }
+  };
+  results
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn strings(args:&[&str]) -> Vec<String> { args.iter().map(|a| String::from(*a)).collect() }
+
+  #[test]
+  fn child_args_strip_the_parent_options() {
+    let args = strings(&["run", "--isolate", "--timestamp", "--size", "10",
+                         "--timeout", "60", "-L", "a,b", "--exclude", "c", "--tag", "lazy",
+                         "--compare", "base.csv", "--tolerance", "0.2", "--out", "res",
+                         "--name", "n", "--format", "html,json", "--seeds", "0..3", "--shrink"]);
+    assert_eq!(child_args(&args, "res/n", "list-a", &vec![OutputFormat::Html, OutputFormat::Json]),
+               strings(&["run", "--size", "10", "--seeds", "0..3", "--shrink",
+                         "--out", "res/n", "--lab", "list-a", "--format", "html,json,csv"]));
+  }
+
+  #[test]
+  fn child_args_strip_the_parent_options_with_values_attached() {
+    let args = strings(&["run", "--lab=a", "-La", "--timeout=5", "--out=res", "--format=csv", "--isolate"]);
+    assert_eq!(child_args(&args, "res", "a", &vec![OutputFormat::Csv]),
+               strings(&["run", "--out", "res", "--lab", "a", "--format", "csv"]));
+  }
+
+  #[test]
+  fn child_args_append_out_lab_and_format_once() {
+    let args = strings(&["bench", "--isolate"]);
+    let child = child_args(&args, "res", "a", &vec![OutputFormat::Csv, OutputFormat::Tsv]);
+    assert_eq!(child, strings(&["bench", "--out", "res", "--lab", "a", "--format", "csv,tsv"]));
+    // A child's own arguments give the same child
+    assert_eq!(child_args(&child, "res", "a", &vec![OutputFormat::Csv, OutputFormat::Tsv]), child);
+    for opt in ["--out", "--lab", "--format"].iter() {
+      assert_eq!(child.iter().filter(|a| a == opt).count(), 1)
+    };
+  }
+}
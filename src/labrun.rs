@@ -7,9 +7,14 @@ use std::marker::PhantomData;
 
 use adapton::engine::*;
 use adapton::reflect;
+use adapton::reflect::trace;
 use adapton::engine::manage::*;
 use rand::{Rng, SeedableRng};
 use std::mem::swap;
+use std::any::Any;
+use std::cell::Cell;
+use std::panic;
+use std::panic::{catch_unwind, AssertUnwindSafe};
 
 pub trait SampleGen {
   fn sample(self:&mut Self) -> Option<Sample>;
@@ -34,21 +39,140 @@ pub struct LabState<R:Rng+Clone,
   pub change_batch_num: usize,
   pub dcg_state:   LabEngineState<Input,EditSt,Output,Editor,Archivist>,
   pub naive_state: LabEngineState<Input,EditSt,Output,Editor,Archivist>,
+  /// Additional DCGs that follow the same trajectory as `dcg_state`,
+  /// only for timing; See `SampleParams::timing_reps`.
+  pub dcg_replicas: Vec<LabEngineState<Input,EditSt,Output,Editor,Archivist>>,
   pub samples:     Vec<Sample>,
+  /// The input seed of this trajectory.
+  pub seed:        usize,
+  /// Set when a sample panics; the trajectory ends there.
+  pub failure:     Option<LabFailure>,
+  /// The recorded edits of each change batch so far, if recording
+  /// (See `SampleParams::record_edits`); None if not recording, or
+  /// if the Editor does not describe its edits.
+  pub edits:       Option<Vec<Vec<String>>>,
+}
+
+thread_local!(
+  /// True while we are reflecting a trace, between
+  /// `reflect::dcg_reflect_begin` and `reflect::dcg_reflect_end`.
+  static TRACING: Cell<bool> = Cell::new(false);
+  /// True after a panic leaves Adapton's trace state inconsistent on
+  /// this thread (See `recover_from_panic`); we stop tracing.
+  static TRACING_BROKEN: Cell<bool> = Cell::new(false);
+);
+
+/// The message of a panic, if it has one.
+pub fn panic_message(payload:&Box<Any + Send>) -> String {
+  match payload.downcast_ref::<&str>() {
+    Some(s) => String::from(*s),
+    None => match payload.downcast_ref::<String>() {
+      Some(s) => s.clone(),
+      None => String::from("(panic without a message)"),
+    }
+  }
+}
+
+/// After catching a panic, swaps out the engine in use (e.g., a DCG
+/// in an inconsistent state) for the Naive engine, and ends any trace
+/// reflection that the panic interrupted.  If the panic happened
+/// within a nested effect of the trace, Adapton cannot end the
+/// reflection, and we stop tracing the remaining samples on this
+/// thread.
+fn recover_from_panic() {
+  let _ = use_engine(Engine::Naive);
+  if TRACING.with(|t| t.replace(false)) {
+    // Silence the expected panic of a failed attempt
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| ()));
+    let ended = catch_unwind(|| reflect::dcg_reflect_end());
+    panic::set_hook(hook);
+    match ended {
+      Ok(_) => (),
+      Err(_) => {
+        println!("Warning: a panic interrupted a reflected trace; tracing is now off.");
+        TRACING_BROKEN.with(|t| t.set(true))
+      }
+    }
+  }
+}
+
+/// Summarizes the timed runs of a sample.
+pub fn timing_stats(samples:&[u64]) -> TimingStats {
+  if samples.len() == 0 {
+    return TimingStats{ count:0, min_ns:0, median_ns:0, mean_ns:0.0, stddev_ns:0.0, p95_ns:0 }
+  };
+  let mut sorted = samples.to_vec();
+  sorted.sort();
+  let n = sorted.len();
+  let median_ns = if n % 2 == 1 { sorted[n / 2] } else { (sorted[n / 2 - 1] + sorted[n / 2]) / 2 };
+  let mean_ns   = sorted.iter().fold(0.0, |sum, &t| sum + (t as f64)) / (n as f64);
+  let variance  = sorted.iter().fold(0.0, |sum, &t| sum + ((t as f64) - mean_ns).powi(2)) / (n as f64);
+  // Nearest-rank percentile: the smallest sample such that 95% of samples are no greater.
+  let p95_rank  = ((0.95 * (n as f64)).ceil() as usize).max(1);
+  TimingStats{
+    count:     n,
+    min_ns:    sorted[0],
+    median_ns: median_ns,
+    mean_ns:   mean_ns,
+    stddev_ns: variance.sqrt(),
+    p95_ns:    sorted[p95_rank - 1],
+  }
 }
 
       
+fn effect_counts_rec(tr:&trace::Trace, c:&mut EffectCounts) {
+  use adapton::reflect::trace::{Effect,AllocCase,AllocKind,ChangeFlag,ForceCase};
+  match tr.effect {
+    Effect::Alloc(AllocCase::LocFresh, AllocKind::RefCell) => c.alloc_fresh_ref += 1,
+    Effect::Alloc(AllocCase::LocFresh, AllocKind::Thunk)   => c.alloc_fresh_thunk += 1,
+    Effect::Alloc(AllocCase::LocExists(ChangeFlag::ContentSame), AllocKind::RefCell) => c.alloc_same_ref += 1,
+    Effect::Alloc(AllocCase::LocExists(ChangeFlag::ContentSame), AllocKind::Thunk)   => c.alloc_same_thunk += 1,
+    Effect::Alloc(AllocCase::LocExists(ChangeFlag::ContentDiff), AllocKind::RefCell) => c.alloc_diff_ref += 1,
+    Effect::Alloc(AllocCase::LocExists(ChangeFlag::ContentDiff), AllocKind::Thunk)   => c.alloc_diff_thunk += 1,
+    Effect::Force(ForceCase::CompCacheMiss) => c.force_cache_miss += 1,
+    Effect::Force(ForceCase::CompCacheHit)  => c.force_cache_hit += 1,
+    Effect::Force(ForceCase::RefGet)        => c.force_ref_get += 1,
+    Effect::Dirty     => c.dirty += 1,
+    Effect::CleanRec  => c.clean_rec += 1,
+    Effect::CleanEdge => c.clean_edge += 1,
+    Effect::CleanEval => c.clean_eval += 1,
+    Effect::Remove    => c.remove += 1,
+    // Debug traces are not effects on the DCG
+    Effect::Debug(..) => (),
+  };
+  for sub_tr in tr.extent.iter() {
+    effect_counts_rec(sub_tr, c)
+  }
+}
+
+/// Counts each kind of effect in a sequence of (reflected) trace trees.
+pub fn effect_counts(traces:&Vec<trace::Trace>) -> EffectCounts {
+  let mut c = EffectCounts::default();
+  for tr in traces.iter() {
+    effect_counts_rec(tr, &mut c)
+  };
+  c
+}
+
 fn get_engine_metrics<X,F:FnOnce() -> X> (params:&SampleParams, thunk:F) -> (X,EngineMetrics)
 {
-  if params.reflect_trace { reflect::dcg_reflect_begin(); };
+  // To count effects, we trace the sample, even if we do not keep the trace.
+  let do_trace = ( params.reflect_trace || params.count_effects ) && !TRACING_BROKEN.with(|t| t.get());
+  if do_trace { reflect::dcg_reflect_begin(); TRACING.with(|t| t.set(true)) };
   let time_start = time::precise_time_ns();
   let x = (thunk)();
   let time_end = time::precise_time_ns();
-  let traces = if params.reflect_trace { reflect::dcg_reflect_end() } else { vec![ ] };
+  let traces = if do_trace { TRACING.with(|t| t.set(false)); reflect::dcg_reflect_end() } else { vec![ ] };
+  let counts = if params.count_effects { effect_counts(&traces) } else { EffectCounts::default() };
+  let traces = if params.reflect_trace { traces } else { vec![ ] };
   let dcg    = if params.reflect_dcg   { reflect::dcg_reflect_now() } else { None };
   return (x, EngineMetrics{
     time_ns:time_end - time_start,
-    //engine_cnt:cnt,
+    time_ns_samples:vec![time_end - time_start],
+    time_stats:timing_stats(&[time_end - time_start]),
+    batch_times_ns:vec![],
+    effect_counts:counts,
     reflect_traces:traces,
     reflect_dcg:dcg,
   })
@@ -61,20 +185,54 @@ fn get_engine_sample
    Editor:Generate<Input>+Edit<Input,EditSt>,
    Archivist:ComputeDemand<Input,Output>
    > 
-  (rng:&mut R, params:&SampleParams, input:Option<(Input,EditSt)>) -> (Output,Input,EditSt,EngineSample) 
+  (rng:&mut R, params:&SampleParams, input:Option<(Input,EditSt)>,
+   replay:Option<&Vec<String>>, recorded:&mut Option<Vec<String>>) -> (Output,Input,EditSt,EngineSample) 
 {
   let mut rng2 = rng;
   
-  let ((edited_input, editst), process_input) : ((Input,EditSt),EngineMetrics) = 
+  let (((edited_input, editst), batch_times_ns), mut process_input) : (((Input,EditSt),Vec<u64>),EngineMetrics) = 
     match input {
       None => 
         get_engine_metrics( params,
-          move || ( Editor::generate(&mut rng2, &params.generate_params), 
-                    Editor::edit_init(&mut rng2, &params.generate_params ))),
+          move || (( Editor::generate(&mut rng2, &params.generate_params), 
+                     Editor::edit_init(&mut rng2, &params.generate_params )), vec![])),
       Some((input, editst)) => 
         get_engine_metrics( params,
-          move || Editor::edit(input, editst, &mut rng2, &params.generate_params))
+          move || {
+            // Apply a full batch of edits, threading the editor state
+            // and the Rng through each one, before the archivist
+            // recomputes.  We time each edit individually.  When
+            // replaying, the recorded edits replace the random ones.
+            let mut edited = (input, editst);
+            let mut times  = Vec::with_capacity(params.change_batch_size);
+            match replay {
+              Some(edits) => for edit in edits.iter() {
+                let time_start = time::precise_time_ns();
+                edited = Editor::replay_edit(edited.0, edited.1, edit, &params.generate_params);
+                let time_end = time::precise_time_ns();
+                times.push(time_end - time_start);
+                match *recorded { Some(ref mut edits) => edits.push(edit.clone()), None => () };
+              },
+              None => for _ in 0..params.change_batch_size {
+                let time_start = time::precise_time_ns();
+                edited = if recorded.is_some() {
+                  let (t, s, edit) = Editor::edit_recorded(edited.0, edited.1, &mut rng2, &params.generate_params);
+                  *recorded = match (recorded.take(), edit) {
+                    (Some(mut edits), Some(edit)) => { edits.push(edit); Some(edits) },
+                    _ => None,
+                  };
+                  (t, s)
+                } else {
+                  Editor::edit(edited.0, edited.1, &mut rng2, &params.generate_params)
+                };
+                let time_end = time::precise_time_ns();
+                times.push(time_end - time_start);
+              },
+            };
+            (edited, times)
+          })
     };
+  process_input.batch_times_ns = batch_times_ns;
 
   let input2  = edited_input.clone();
   
@@ -83,7 +241,17 @@ fn get_engine_sample
       Some(reflect::reflect_val(&input2)) 
     } else { None };
 
-  let (output, compute_output): (Output,EngineMetrics) 
+  // The naive computation is pure, so we can warm it up, and re-run
+  // it over the same input for more timing samples.
+  let is_naive = engine_is_naive();
+  if is_naive {
+    for _ in 0..params.timing_warmup {
+      let input3 = edited_input.clone();
+      drop(ns(name_of_str("compute"), move || Archivist::compute(input3, params.demand)))
+    }
+  };
+
+  let (output, mut compute_output): (Output,EngineMetrics) 
     = ns(name_of_str("compute"),
          move || 
          get_engine_metrics( 
@@ -91,6 +259,17 @@ fn get_engine_sample
              Archivist::compute(input2, params.demand) 
          ));
 
+  if is_naive && params.timing_reps > 1 {
+    for _ in 1..params.timing_reps {
+      let input3 = edited_input.clone();
+      let time_start = time::precise_time_ns();
+      drop(ns(name_of_str("compute"), move || Archivist::compute(input3, params.demand)));
+      let time_end = time::precise_time_ns();
+      compute_output.time_ns_samples.push(time_end - time_start);
+    };
+    compute_output.time_stats = timing_stats(&compute_output.time_ns_samples);
+  };
+
   let outputr = 
     if params.reflect_dcg { 
       Some(reflect::reflect_val(&output)) 
@@ -112,14 +291,23 @@ fn get_sample_gen
    Output:Eq+Debug,
    Editor:Generate<Input>+Edit<Input,EditSt>,
    Archivist:ComputeDemand<Input,Output>> 
-  (params:&LabParams) 
+  (params:&LabParams, seed:usize) 
    -> LabState<rand::StdRng,Input,EditSt,Output,Editor,Archivist> 
 {
   // Create empty DCG; TODO-Minor-- Make the API for this better.
   let _ = init_dcg(); assert!(engine_is_dcg());
   let empty_dcg = use_engine(Engine::Naive); // TODO-Minor: Rename this operation: "engine_swap" or something 
-  let rng = SeedableRng::from_seed(params.sample_params.input_seeds.as_slice());
+  let rng = SeedableRng::from_seed(&[seed][..]);
   //let editst_init = Editor::edit_init(&mut rng, & params.sample_params.generate_params);
+  let mut dcg_replicas = vec![];
+  for _ in 1..params.sample_params.timing_reps {
+    let _ = init_dcg(); assert!(engine_is_dcg());
+    dcg_replicas.push(LabEngineState{
+      input:  None,
+      engine: use_engine(Engine::Naive), // empty DCG
+      output: PhantomData, inputdist: PhantomData, computer: PhantomData,
+    })
+  };
   LabState{
     params:params.clone(),
     rng:Box::new(rng),
@@ -134,10 +322,42 @@ fn get_sample_gen
       output: PhantomData, inputdist: PhantomData, computer: PhantomData,
     },
     change_batch_num: 0,
+    dcg_replicas,
     samples:vec![],
+    seed,
+    failure:None,
+    edits: if params.sample_params.record_edits { Some(vec![]) } else { None },
   }
 }
 
+impl<R:Rng+Clone,Input,EditSt,Output,
+     Editor:Generate<Input>+Edit<Input,EditSt>,
+     Archivist:ComputeDemand<Input,Output>>
+  LabState<R,Input,EditSt,Output,Editor,Archivist> {
+    /// Records a panic of the current sample as the failure of this
+    /// trajectory, which ends it.
+    fn fail(self:&mut Self, engine:EngineKind, payload:Box<Any + Send>) -> Option<Sample> {
+      recover_from_panic();
+      self.failure = Some(LabFailure{
+        message:    panic_message(&payload),
+        engine:     Some(engine),
+        seed:       Some(self.seed),
+        batch_name: Some(self.change_batch_num),
+      });
+      None
+    }
+
+    /// Records the edits of the current change batch, if any (See
+    /// `get_engine_sample`); None if the Editor did not describe them.
+    fn record(self:&mut Self, recorded:Option<Vec<String>>) {
+      if self.change_batch_num == 0 { return }; // Generating, not editing
+      self.edits = match (self.edits.take(), recorded) {
+        (Some(mut batches), Some(edits)) => { batches.push(edits); Some(batches) },
+        _ => None,
+      }
+    }
+  }
+
 /// Advances the LabState forward by one sample of each engine.  For
 /// each engine, we process the current input (either generating it,
 /// or editing it) and we compute a new output over this processed input.
@@ -147,9 +367,15 @@ impl<Input:Clone+Debug,EditSt,Output:Eq+Debug,
      Archivist:ComputeDemand<Input,Output>>
   SampleGen for LabState<rand::StdRng,Input,EditSt,Output,Editor,Archivist> {
     fn sample (self:&mut Self) -> Option<Sample> {
-      if self.change_batch_num > self.params.change_batch_loopc {
+      if self.change_batch_num > self.params.change_batch_loopc || self.failure.is_some() {
         None 
       } else { // Collect the next sample, for each engine, using get_engine_sample.
+        // When replaying, the recorded edits of this batch
+        let replay : Option<Vec<String>> = match self.params.replay_edits {
+          Some(ref script) if self.change_batch_num > 0 =>
+            Some(script.batches[self.change_batch_num - 1].clone()),
+          _ => None,
+        };
         let mut dcg_state = LabEngineState{ input: None, engine: Engine::Naive, 
                                              output: PhantomData, inputdist: PhantomData, computer: PhantomData };
         swap(&mut dcg_state, &mut self.dcg_state );
@@ -161,9 +387,18 @@ impl<Input:Clone+Debug,EditSt,Output:Eq+Debug,
         //println!("Naive - - - - - ({:?} / {:?})", self.change_batch_num, self.params.change_batch_loopc );
         let _ = use_engine(Engine::Naive); assert!(engine_is_naive());
         let mut rng = self.rng.clone(); // Restore Rng
-        let (naive_output, naive_input_edited, naive_editst, naive_sample) = 
+        // Record the edits of the Naive engine; the other engines make the same ones
+        let mut recorded = if self.edits.is_some() { Some(vec![]) } else { None };
+        let naive_result = catch_unwind(AssertUnwindSafe(|| {
           get_engine_sample::<rand::StdRng,Input,EditSt,Output,Editor,Archivist>
-          (&mut rng, &self.params.sample_params, naive_state.input);
+            (&mut rng, &self.params.sample_params, naive_state.input, replay.as_ref(), &mut recorded)
+        }));
+        self.record(recorded); // Even if it failed
+        let (naive_output, naive_input_edited, naive_editst, naive_sample) = 
+          match naive_result {
+            Ok(s) => s,
+            Err(payload) => return self.fail(EngineKind::Naive, payload),
+          };
         self.naive_state.input = Some((naive_input_edited, naive_editst)); // Save the input and input-editing state
 
         // Run DCG Version
@@ -171,11 +406,51 @@ impl<Input:Clone+Debug,EditSt,Output:Eq+Debug,
         let _ = use_engine(dcg_state.engine); // Restore saved DCG
         assert!(engine_is_dcg()); // This really is the DCG version
         let mut rng = self.rng.clone(); // Restore Rng
-        let (dcg_output, dcg_input_edited, dcg_editst, dcg_sample) = 
-          get_engine_sample::<rand::StdRng,Input,EditSt,Output,Editor,Archivist>
-          (&mut rng, &self.params.sample_params, dcg_state.input);
+        let dcg_input = dcg_state.input;
+        let (dcg_output, dcg_input_edited, dcg_editst, mut dcg_sample) = 
+          match catch_unwind(AssertUnwindSafe(|| {
+            get_engine_sample::<rand::StdRng,Input,EditSt,Output,Editor,Archivist>
+              (&mut rng, &self.params.sample_params, dcg_input, replay.as_ref(), &mut None)
+          })) {
+            Ok(s) => s,
+            Err(payload) => return self.fail(EngineKind::DCG, payload),
+          };
         self.dcg_state.engine = use_engine(Engine::Naive); // Swap out the DCG
         self.dcg_state.input = Some((dcg_input_edited, dcg_editst)); // Save the input and input-editing state
+
+        // Replay this sample on each DCG replica, only for timing
+        if self.dcg_replicas.len() > 0 {
+          let mut replica_params = self.params.sample_params.clone();
+          replica_params.reflect_trace = false;
+          replica_params.reflect_dcg   = false;
+          replica_params.count_effects = false;
+          replica_params.record_edits  = false;
+          let mut replicas = vec![];
+          swap(&mut replicas, &mut self.dcg_replicas);
+          for replica in replicas.into_iter() {
+            let _ = use_engine(replica.engine); // Restore saved DCG replica
+            assert!(engine_is_dcg());
+            let mut replica_rng = self.rng.clone(); // Restore Rng
+            let replica_input = replica.input;
+            let (_, replica_input_edited, replica_editst, replica_sample) = 
+              match catch_unwind(AssertUnwindSafe(|| {
+                get_engine_sample::<rand::StdRng,Input,EditSt,Output,Editor,Archivist>
+                  (&mut replica_rng, &replica_params, replica_input, replay.as_ref(), &mut None)
+              })) {
+                Ok(s) => s,
+                Err(payload) => return self.fail(EngineKind::DCG, payload),
+              };
+            dcg_sample.process_input.time_ns_samples.push(replica_sample.process_input.time_ns);
+            dcg_sample.compute_output.time_ns_samples.push(replica_sample.compute_output.time_ns);
+            self.dcg_replicas.push(LabEngineState{
+              input:  Some((replica_input_edited, replica_editst)),
+              engine: use_engine(Engine::Naive), // Swap out the DCG replica
+              output: PhantomData, inputdist: PhantomData, computer: PhantomData,
+            })
+          };
+          dcg_sample.process_input.time_stats  = timing_stats(&dcg_sample.process_input.time_ns_samples);
+          dcg_sample.compute_output.time_stats = timing_stats(&dcg_sample.compute_output.time_ns_samples);
+        };
         
         // Save the Rng for the next sample.
         self.rng = Box::new(*rng);
@@ -184,6 +459,9 @@ impl<Input:Clone+Debug,EditSt,Output:Eq+Debug,
         let output_valid = if self.params.sample_params.validate_output { 
           Some ( dcg_output == naive_output )
         } else { None } ;
+        let output_mismatch = if output_valid == Some(false) {
+          Some((format!("{:?}", dcg_output), format!("{:?}", naive_output)))
+        } else { None } ;
 
         let sample = Sample{
           //params:self.params.sample_params.clone(),
@@ -191,6 +469,7 @@ impl<Input:Clone+Debug,EditSt,Output:Eq+Debug,
           dcg_sample,
           naive_sample,
           output_valid,
+          output_mismatch,
         };
         self.change_batch_num += 1;
         Some(sample)
@@ -206,22 +485,335 @@ impl<Input:Clone+Debug,EditSt,Output:Eq+Debug,
   Lab for LabDef<Input,EditSt,Output,Editor,Archivist> {
     fn name(self:&Self) -> Name { self.identity.clone() }
     fn url(self:&Self) -> &Option<String> { &self.url }
+    fn info(self:&Self) -> &LabInfo { &self.info }
+    fn types(self:&Self) -> &LabTypes { &self.types }
     fn run(self:&Self, params:&LabParams) -> LabResults 
-    {            
-      let mut st = get_sample_gen::<Input,EditSt,Output,Editor,Archivist>(params);
-      loop {
-        //println!("{:?}", self.name());
-        let sample = (&mut st).sample();
-        //println!("{:?}", sample);        
-
-        // TODO: Dump to CSV/Tab-separated file; e.g., for GNUPLOT
-        match sample {
-          Some(s) => {st.samples.push(s); continue},
-          None => break,
+    {
+      // A script determines the input seed, the input and the change batches
+      let params = &match params.replay_edits {
+        None => params.clone(),
+        Some(ref script) => {
+          let mut p = params.clone();
+          p.sample_params.input_seeds     = vec![script.seed];
+          p.sample_params.generate_params = script.generate_params.clone();
+          p.change_batch_loopc = script.batches.len();
+          p
+        }
+      };
+      let mut trajectories = vec![];
+      // One independent trajectory per seed, each with a fresh DCG
+      for &seed in params.sample_params.input_seeds.iter() {
+        // Each sample catches its own panics (See `LabState::fail`);
+        // here, we catch any others.
+        let trajectory = catch_unwind(AssertUnwindSafe(|| {
+          let mut st = get_sample_gen::<Input,EditSt,Output,Editor,Archivist>(params, seed);
+          loop {
+            //println!("{:?}", self.name());
+            let sample = (&mut st).sample();
+            //println!("{:?}", sample);        
+
+            match sample {
+              Some(s) => {st.samples.push(s); continue},
+              None => break,
+            }
+          };
+          let edit_script = st.edits.map(|batches| EditScript{
+            labname: reflect::string_of_name( &self.name() ),
+            seed,
+            generate_params: params.sample_params.generate_params.clone(),
+            batches,
+          });
+          Trajectory{ seed, samples: st.samples, failure: st.failure, edit_script }
+        }));
+        match trajectory {
+          Ok(t) => trajectories.push(t),
+          Err(payload) => {
+            recover_from_panic();
+            trajectories.push(Trajectory{
+              seed, samples: vec![], edit_script: None,
+              failure: Some(LabFailure{ message: panic_message(&payload), engine: None, seed: Some(seed), batch_name: None }),
+            })
+          }
         }
       };
       return LabResults {
-        samples: st.samples,
+        trajectories,
+        failure: None,
       }
     }
   }
+
+/// The first sample of the results whose DCG and naive outputs
+/// disagree, as a pair of its input seed and its batch name.
+pub fn first_failure(results:&LabResults) -> Option<(usize, usize)> {
+  for trajectory in results.trajectories.iter() {
+    for sample in trajectory.samples.iter() {
+      if sample.output_valid == Some(false) {
+        return Some((trajectory.seed, sample.batch_name))
+      }
+    }
+  };
+  None
+}
+
+/// Candidates for shrinking `x` towards `lo`, smallest first: `lo`,
+/// then halving the distance to `x`, ending with `x - 1`.
+fn shrink_candidates(x:usize, lo:usize) -> Vec<usize> {
+  let mut cs = vec![];
+  let mut d = if x > lo { x - lo } else { 0 };
+  while d > 0 { cs.push(x - d); d = d / 2 };
+  cs
+}
+
+fn size_of_params(p:&mut LabParams) -> &mut usize { &mut p.sample_params.generate_params.size }
+fn batch_size_of_params(p:&mut LabParams) -> &mut usize { &mut p.sample_params.change_batch_size }
+
+/// Shrinks one parameter, in place, to the first candidate that still
+/// fails; returns true if it found one.
+fn shrink_param(lab:&Box<Lab>, params:&mut LabParams, batch_name:&mut usize, runs:&mut usize,
+                lo:usize, param:fn(&mut LabParams) -> &mut usize) -> bool {
+  let x = *param(params);
+  for c in shrink_candidates(x, lo) {
+    let mut p = params.clone();
+    *param(&mut p) = c;
+    *runs += 1;
+    match first_failure(&lab.run(&p)) {
+      None => continue,
+      Some((_, b)) => {
+        // Later batches are irrelevant to this failure
+        p.change_batch_loopc = b;
+        *params = p; *batch_name = b;
+        return true
+      }
+    }
+  };
+  false
+}
+
+/// On a from-scratch-consistency failure in the results (See
+/// `first_failure`), searches for a minimal counterexample, by
+/// re-running the lab with smaller parameters: Only the failing input
+/// seed, a smaller input size (`GenerateParams::size`), smaller
+/// change batches (`SampleParams::change_batch_size`), and no change
+/// batches after the first failing one (`change_batch_loopc`).  The
+/// search is greedy: it shrinks each parameter until none of them
+/// shrink further while the failure persists.  We turn off reflection
+/// and repeated timing, which do not affect the outputs.  Returns
+/// None if the results have no failure, or if they replay an edit
+/// script, whose edits we cannot shrink.
+pub fn shrink_failure(lab:&Box<Lab>, params:&LabParams, results:&LabResults) -> Option<ShrinkResult> {
+  if params.replay_edits.is_some() { return None };
+  let (seed, mut batch_name) = match first_failure(results) {
+    None => return None,
+    Some(f) => f,
+  };
+  let mut p = params.clone();
+  p.sample_params.input_seeds     = vec![seed];
+  p.sample_params.validate_output = true;
+  p.sample_params.reflect_trace   = false;
+  p.sample_params.reflect_dcg     = false;
+  p.sample_params.count_effects   = false;
+  p.sample_params.record_edits    = false;
+  p.sample_params.timing_warmup   = 0;
+  p.sample_params.timing_reps     = 1;
+  p.change_batch_loopc = batch_name;
+  let mut runs = 0;
+  loop {
+    let shrunk_size  = shrink_param(lab, &mut p, &mut batch_name, &mut runs, 1, size_of_params);
+    let shrunk_batch = shrink_param(lab, &mut p, &mut batch_name, &mut runs, 1, batch_size_of_params);
+    if !shrunk_size && !shrunk_batch { break }
+  };
+  Some(ShrinkResult{ params:p, seed, batch_name, runs })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::cell::RefCell;
+  use std::rc::Rc;
+  use labmain::lab_params_defaults;
+
+  /// A lab whose outputs disagree when `fails` holds, given the input
+  /// seed, the input size, the change batch size and the batch name;
+  /// it records the input size and batch size of each run.
+  struct FailingLab {
+    info:  LabInfo,
+    types: LabTypes,
+    fails: fn(usize, usize, usize, usize) -> bool,
+    runs:  Rc<RefCell<Vec<(usize, usize)>>>,
+  }
+
+  impl Lab for FailingLab {
+    fn name(self:&Self) -> Name { name_of_str("failing") }
+    fn url(self:&Self) -> &Option<String> { &None }
+    fn info(self:&Self) -> &LabInfo { &self.info }
+    fn types(self:&Self) -> &LabTypes { &self.types }
+    fn run(self:&Self, params:&LabParams) -> LabResults {
+      let size       = params.sample_params.generate_params.size;
+      let batch_size = params.sample_params.change_batch_size;
+      self.runs.borrow_mut().push((size, batch_size));
+      let trajectories = params.sample_params.input_seeds.iter().map(|&seed| {
+        let samples = (0..params.change_batch_loopc + 1).map(|b| {
+          sample(b, !(self.fails)(seed, size, batch_size, b))
+        }).collect();
+        Trajectory{ seed, samples, failure: None, edit_script: None }
+      }).collect();
+      LabResults{ trajectories, failure: None }
+    }
+  }
+
+  /// The lab, and the record of its runs.
+  fn failing_lab(fails:fn(usize, usize, usize, usize) -> bool) -> (Box<Lab>, Rc<RefCell<Vec<(usize, usize)>>>) {
+    let types = LabTypes{ input:"", editst:"", output:"", editor:"", archivist:"" };
+    let runs  = Rc::new(RefCell::new(vec![]));
+    (Box::new(FailingLab{ info:LabInfo::default(), types, fails, runs:runs.clone() }), runs)
+  }
+
+  fn engine_sample() -> EngineSample {
+    let metrics = EngineMetrics{
+      time_ns: 0, time_ns_samples: vec![], time_stats: timing_stats(&[]), batch_times_ns: vec![],
+      effect_counts: EffectCounts::default(), reflect_traces: vec![], reflect_dcg: None,
+    };
+    EngineSample{ process_input: metrics.clone(), compute_output: metrics, input: None, output: None }
+  }
+
+  fn sample(batch_name:usize, valid:bool) -> Sample {
+    Sample{ batch_name, dcg_sample: engine_sample(), naive_sample: engine_sample(),
+            output_valid: Some(valid), output_mismatch: None }
+  }
+
+  /// Input seed 7 fails from batch 2 on, with an input size of at
+  /// least 5 and change batches of at least 3.
+  fn fails(seed:usize, size:usize, batch_size:usize, batch:usize) -> bool {
+    seed == 7 && size >= 5 && batch_size >= 3 && batch >= 2
+  }
+
+  fn failing_params() -> LabParams {
+    let mut params = lab_params_defaults();
+    params.sample_params.input_seeds = vec![0, 7];
+    params.sample_params.generate_params.size = 20;
+    params.sample_params.change_batch_size = 8;
+    params.change_batch_loopc = 10;
+    params
+  }
+
+  #[test]
+  fn shrink_candidates_smallest_first() {
+    assert_eq!(shrink_candidates(20, 1), vec![1, 11, 16, 18, 19]);
+    assert_eq!(shrink_candidates(8, 1),  vec![1, 5, 7]);
+    assert_eq!(shrink_candidates(2, 1),  vec![1]);
+    assert_eq!(shrink_candidates(1, 1),  vec![]);
+    assert_eq!(shrink_candidates(0, 1),  vec![]);
+  }
+
+  #[test]
+  fn shrink_param_takes_the_first_failing_candidate() {
+    let (lab, lab_runs) = failing_lab(fails);
+    let mut params = failing_params();
+    params.sample_params.input_seeds = vec![7];
+    let (mut batch_name, mut runs) = (10, 0);
+    assert!(shrink_param(&lab, &mut params, &mut batch_name, &mut runs, 1, size_of_params));
+    // Size 1 passes, size 11 fails; we stop there, with its first failing batch
+    assert_eq!(*lab_runs.borrow(), vec![(1, 8), (11, 8)]);
+    assert_eq!(runs, 2);
+    assert_eq!(params.sample_params.generate_params.size, 11);
+    assert_eq!((batch_name, params.change_batch_loopc), (2, 2));
+  }
+
+  #[test]
+  fn shrink_param_stops_when_no_candidate_fails() {
+    let (lab, lab_runs) = failing_lab(fails);
+    let mut params = failing_params();
+    params.sample_params.input_seeds = vec![7];
+    params.sample_params.generate_params.size = 5;
+    let (mut batch_name, mut runs) = (2, 0);
+    assert!(!shrink_param(&lab, &mut params, &mut batch_name, &mut runs, 1, size_of_params));
+    assert_eq!(*lab_runs.borrow(), vec![(1, 8), (3, 8), (4, 8)]);
+    assert_eq!(runs, 3);
+    // The parameters are those of the last failing run
+    assert_eq!(params.sample_params.generate_params.size, 5);
+    assert_eq!(batch_name, 2);
+  }
+
+  #[test]
+  fn shrink_failure_finds_the_minimal_reproducer() {
+    let (lab, lab_runs) = failing_lab(fails);
+    let params = failing_params();
+    let results = lab.run(&params);
+    assert_eq!(first_failure(&results), Some((7, 2)));
+    let shrunk = shrink_failure(&lab, &params, &results).unwrap();
+    assert_eq!((shrunk.seed, shrunk.batch_name), (7, 2));
+    assert_eq!(shrunk.params.sample_params.input_seeds, vec![7]);
+    assert_eq!(shrunk.params.sample_params.generate_params.size, 5);
+    assert_eq!(shrunk.params.sample_params.change_batch_size, 3);
+    assert_eq!(shrunk.params.change_batch_loopc, 2);
+    // The first run is ours, above
+    assert_eq!(shrunk.runs, lab_runs.borrow().len() - 1);
+    // The reproducer fails, but not with any smaller parameter
+    assert_eq!(first_failure(&lab.run(&shrunk.params)), Some((7, 2)));
+    let mut p = shrunk.params.clone();
+    p.sample_params.generate_params.size = 4;
+    assert_eq!(first_failure(&lab.run(&p)), None);
+    let mut p = shrunk.params.clone();
+    p.sample_params.change_batch_size = 2;
+    assert_eq!(first_failure(&lab.run(&p)), None);
+  }
+
+  #[test]
+  fn shrink_failure_without_a_failure() {
+    let (lab, lab_runs) = failing_lab(fails);
+    let mut params = failing_params();
+    params.sample_params.input_seeds = vec![0];
+    let results = lab.run(&params);
+    assert!(shrink_failure(&lab, &params, &results).is_none());
+    assert_eq!(lab_runs.borrow().len(), 1);
+  }
+
+  #[test]
+  fn timing_stats_of_no_samples() {
+    let s = timing_stats(&[]);
+    assert_eq!((s.count, s.min_ns, s.median_ns, s.p95_ns), (0, 0, 0, 0));
+    assert_eq!((s.mean_ns, s.stddev_ns), (0.0, 0.0));
+  }
+
+  #[test]
+  fn timing_stats_of_one_rep() {
+    let s = timing_stats(&[42]);
+    assert_eq!((s.count, s.min_ns, s.median_ns, s.p95_ns), (1, 42, 42, 42));
+    assert_eq!((s.mean_ns, s.stddev_ns), (42.0, 0.0));
+  }
+
+  #[test]
+  fn timing_stats_of_an_odd_count() {
+    // Unsorted, as timed
+    let s = timing_stats(&[30, 10, 50, 20, 40]);
+    assert_eq!(s.count, 5);
+    assert_eq!(s.min_ns, 10);
+    assert_eq!(s.median_ns, 30);
+    // With fewer than 20 samples, the 95th percentile is the maximum
+    assert_eq!(s.p95_ns, 50);
+    assert_eq!(s.mean_ns, 30.0);
+    assert_eq!(s.stddev_ns, 200f64.sqrt());
+  }
+
+  #[test]
+  fn timing_stats_of_an_even_count() {
+    // The median of an even count is the mean of the middle two samples
+    let s = timing_stats(&[8, 2, 6, 4]);
+    assert_eq!(s.count, 4);
+    assert_eq!(s.min_ns, 2);
+    assert_eq!(s.median_ns, 5);
+    assert_eq!(s.p95_ns, 8);
+    assert_eq!(s.mean_ns, 5.0);
+    assert_eq!(s.stddev_ns, 5f64.sqrt());
+  }
+
+  #[test]
+  fn timing_stats_p95_by_nearest_rank() {
+    // Of 1..=40, the 38th smallest is the smallest with 95% no greater
+    let samples : Vec<u64> = (1..41).rev().collect();
+    let s = timing_stats(&samples);
+    assert_eq!((s.count, s.min_ns, s.median_ns, s.p95_ns), (40, 1, 20, 38));
+    assert_eq!(s.mean_ns, 20.5);
+  }
+}
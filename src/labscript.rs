@@ -0,0 +1,178 @@
+use std::fs;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::fs::File;
+use std::io::BufReader;
+
+use adapton::reflect::string_of_name;
+use labdef::{Lab,LabParams,LabResults,EditScript,GenerateParams,NominalStrategy,MAX_SIZE,string_of_nominal_strategy};
+
+/// Writes an edit script as text, one line per item, each consisting
+/// of a key and a value, separated by a tab: First, the lab name
+/// (`lab`), the input seed (`seed`) and the generation parameters
+/// (`size`, `gauge` and `nominal`); then, for each change batch, a
+/// line `batch`, followed by one line `edit` for each of its edits.
+/// Lines that start with `#` are comments.  For example:
+///
+/// ```text
+/// lab	list-eager-map
+/// seed	0
+/// size	8
+/// gauge	1
+/// nominal	regular
+/// batch
+/// edit	prepend 293
+/// batch
+/// edit	prepend 651
+/// ```
+pub fn write_edit_script<W:Write>(writer:&mut W, script:&EditScript) {
+  let gp = &script.generate_params;
+  writeln!(writer, "# Adapton Lab edit script; replay with `--replay <file>`").unwrap();
+  writeln!(writer, "lab\t{}", script.labname).unwrap();
+  writeln!(writer, "seed\t{}", script.seed).unwrap();
+  writeln!(writer, "size\t{}", gp.size).unwrap();
+  writeln!(writer, "gauge\t{}", gp.gauge).unwrap();
+  writeln!(writer, "nominal\t{}", string_of_nominal_strategy(&gp.nominal_strategy)).unwrap();
+  for batch in script.batches.iter() {
+    writeln!(writer, "batch").unwrap();
+    for edit in batch.iter() {
+      writeln!(writer, "edit\t{}", edit).unwrap();
+    }
+  }
+}
+
+/// Reads an edit script written by `write_edit_script`.  The size
+/// must be from 1 to `MAX_SIZE`, and the gauge at least 1.
+pub fn read_edit_script<R:BufRead>(reader:R) -> Result<EditScript, String> {
+  let mut labname = None;
+  let mut seed    = None;
+  let mut gp = GenerateParams{ size:0, gauge:1, nominal_strategy:NominalStrategy::Regular };
+  let mut batches : Vec<Vec<String>> = vec![];
+  for (i, line) in reader.lines().enumerate() {
+    let line = line.map_err(|e| format!("line {}: {}", i + 1, e))?;
+    if line.trim().len() == 0 || line.starts_with("#") { continue };
+    let mut kv = line.splitn(2, '\t');
+    let key = kv.next().unwrap().trim();
+    let val = kv.next().unwrap_or("");
+    let num = |v:&str| v.trim().parse::<usize>().map_err(|_| format!("line {}: expected a number, not `{}`", i + 1, v));
+    let in_range = |x:usize, hi:usize|
+      if x < 1 { Err(format!("line {}: expected a number of at least 1, not `{}`", i + 1, x)) }
+      else if x > hi { Err(format!("line {}: expected a number of at most {}, not `{}`", i + 1, hi, x)) }
+      else { Ok(x) };
+    match key {
+      "lab"     => labname = Some(String::from(val.trim())),
+      "seed"    => seed = Some(num(val)?),
+      "size"    => gp.size = in_range(num(val)?, MAX_SIZE)?,
+      "gauge"   => gp.gauge = in_range(num(val)?, ::std::usize::MAX)?,
+      "nominal" => gp.nominal_strategy = val.trim().parse().map_err(|e| format!("line {}: {}", i + 1, e))?,
+      "batch"   => batches.push(vec![]),
+      "edit"    => match batches.last_mut() {
+        Some(batch) => batch.push(String::from(val)),
+        None => return Err(format!("line {}: an edit before the first batch", i + 1)),
+      },
+      _ => return Err(format!("line {}: unknown key `{}`", i + 1, key)),
+    }
+  };
+  match (labname, seed) {
+    (Some(_), Some(_)) if gp.size == 0 => Err(String::from("missing the input size (`size`)")),
+    (Some(labname), Some(seed)) => Ok(EditScript{ labname, seed, generate_params:gp, batches }),
+    _ => Err(String::from("missing the lab name (`lab`) or the input seed (`seed`)")),
+  }
+}
+
+/// Reads an edit script from a file (See `read_edit_script`).
+pub fn read_edit_script_file(path:&str) -> Result<EditScript, String> {
+  let f = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+  read_edit_script(BufReader::new(f)).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Writes the recorded edits of each trajectory (See
+/// `SampleParams::record_edits`) to
+/// `<output_dir>/<labname>/edits/seed-<seed>.edits`.  To replay a
+/// trajectory exactly, check in this file, and give it to `--replay`.
+/// Warns about trajectories without recorded edits.
+pub fn write_lab_edit_scripts(params:&LabParams, lab:&Box<Lab>, results:&LabResults) {
+  let labname = string_of_name( &lab.name() );
+  for trajectory in results.trajectories.iter() {
+    match trajectory.edit_script {
+      None => {
+        println!("Warning: no recorded edits for lab `{}`, input seed {}; e.g., its Editor does not describe its edits.",
+                 labname, trajectory.seed);
+      },
+      Some(ref script) => {
+        fs::create_dir_all(format!("{}/{}/edits/", params.output_dir, labname)).unwrap();
+        let f = File::create(format!("{}/{}/edits/seed-{}.edits", params.output_dir, labname, trajectory.seed)).unwrap();
+        let mut writer = BufWriter::new(f);
+        write_edit_script(&mut writer, script);
+        writer.flush().unwrap();
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn script() -> EditScript {
+    EditScript{
+      labname: String::from("list-eager-map"),
+      seed: 7,
+      generate_params: GenerateParams{ size:8, gauge:2, nominal_strategy:NominalStrategy::ByContent },
+      batches: vec![vec![String::from("prepend 293")],
+                    vec![],
+                    vec![String::from("remove 3"), String::from("insert 1\tand a tab ")]],
+    }
+  }
+
+  fn read(s:&str) -> Result<EditScript, String> { read_edit_script(s.as_bytes()) }
+
+  #[test]
+  fn round_trip() {
+    let mut buf = vec![];
+    write_edit_script(&mut buf, &script());
+    assert_eq!(read_edit_script(&buf[..]), Ok(script()));
+  }
+
+  #[test]
+  fn comments_and_blank_lines() {
+    assert_eq!(read("# a comment\nlab\tlist-eager-map\n\nseed\t 7 \nsize\t8\ngauge\t2\nnominal\tbycontent\n\
+                     batch\nedit\tprepend 293\nbatch\n  \nbatch\nedit\tremove 3\nedit\tinsert 1\tand a tab \n"),
+               Ok(script()));
+  }
+
+  #[test]
+  fn malformed_lines() {
+    assert_eq!(read("lab\tl\nseed\tx\n"), Err(String::from("line 2: expected a number, not `x`")));
+    assert_eq!(read("lab\tl\nseed\n"), Err(String::from("line 2: expected a number, not ``")));
+    assert_eq!(read("lab\tl\nseed\t-1\n"), Err(String::from("line 2: expected a number, not `-1`")));
+    // The key and the value are separated by a tab, not a space
+    assert_eq!(read("lab\tl\nseed 0\n"), Err(String::from("line 2: unknown key `seed 0`")));
+    assert!(read("lab\tl\nseed\t0\nnominal\tbyname\n").unwrap_err().starts_with("line 3: "));
+    assert_eq!(read("lab\tl\nseed\t0\nedit\tprepend 1\n"), Err(String::from("line 3: an edit before the first batch")));
+    assert_eq!(read("seed\t0\nbatch\n"), Err(String::from("missing the lab name (`lab`) or the input seed (`seed`)")));
+    assert_eq!(read("lab\tl\n"), Err(String::from("missing the lab name (`lab`) or the input seed (`seed`)")));
+  }
+
+  #[test]
+  fn out_of_range() {
+    assert_eq!(read("lab\tl\nseed\t0\nsize\t8\ngauge\t0\n"),
+               Err(String::from("line 4: expected a number of at least 1, not `0`")));
+    assert_eq!(read("lab\tl\nseed\t0\nsize\t0\n"),
+               Err(String::from("line 3: expected a number of at least 1, not `0`")));
+    assert_eq!(read(&format!("lab\tl\nseed\t0\nsize\t{}\n", MAX_SIZE + 1)),
+               Err(format!("line 3: expected a number of at most {}, not `{}`", MAX_SIZE, MAX_SIZE + 1)));
+    assert_eq!(read("lab\tl\nseed\t0\ngauge\t2\n"), Err(String::from("missing the input size (`size`)")));
+    assert_eq!(read(&format!("lab\tl\nseed\t0\nsize\t{}\n", MAX_SIZE)).map(|s| s.generate_params.size), Ok(MAX_SIZE));
+  }
+
+  #[test]
+  fn unknown_kinds() {
+    // An unknown kind of line is an error
+    assert_eq!(read("lab\tl\nseed\t0\nbatch\ninsert\t3\n"), Err(String::from("line 4: unknown key `insert`")));
+    assert_eq!(read("lab\tl\nseed\t0\nbatches\n"), Err(String::from("line 3: unknown key `batches`")));
+    // The kind of each edit is for the lab's Editor to interpret (See `Edit::replay_edit`)
+    assert_eq!(read("lab\tl\nseed\t0\nsize\t8\nbatch\nedit\tfrobnicate 3\n").map(|s| s.batches),
+               Ok(vec![vec![String::from("frobnicate 3")]]));
+  }
+}
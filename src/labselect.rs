@@ -0,0 +1,187 @@
+use regex::Regex;
+
+use adapton::reflect::string_of_name;
+use labdef::Lab;
+
+/// Which labs of a catalog to run (See `select_labs`).
+#[derive(Clone,Debug,Default)]
+pub struct LabSelection {
+  /// Patterns of lab names (See `regex_of_pattern`); a lab matches if
+  /// it matches any of them; with none, every lab matches.
+  pub labs:     Vec<String>,
+  /// Patterns of lab names, as for `labs`, of the labs not to run.
+  pub excludes: Vec<String>,
+  /// Tags (See `LabInfo::tags`); a lab matches if it has all of them.
+  pub tags:     Vec<String>,
+}
+
+impl LabSelection {
+  /// Whether this selects every lab of every catalog.
+  pub fn is_all(&self) -> bool {
+    self.labs.len() == 0 && self.excludes.len() == 0 && self.tags.len() == 0
+  }
+}
+
+/// Parses a comma-separated list (e.g., of patterns or tags), without
+/// empty items.
+pub fn strings_of_list(s:&str) -> Vec<String> {
+  s.split(',').map(|x| String::from(x.trim())).filter(|x| x.len() > 0).collect()
+}
+
+/// The selection of the lists of `--lab`, `--exclude` and `--tag`
+/// (See `strings_of_list`).  Besides patterns, the list of `--lab`
+/// may give patterns of labs not to run, as `!<pattern>` (as
+/// `--exclude` does), and tags, as `tag:<tag>` (as `--tag` does);
+/// e.g., `list-*,!/mergesort/,tag:lazy`.
+pub fn selection_of_lists(labs:&str, excludes:&str, tags:&str) -> LabSelection {
+  let mut selection = LabSelection{ labs: vec![], excludes: strings_of_list(excludes), tags: strings_of_list(tags) };
+  for item in strings_of_list(labs).into_iter() {
+    if item.starts_with("!") { selection.excludes.push(String::from(item[1..].trim())) }
+    else if item.starts_with("tag:") { selection.tags.push(String::from(item[4..].trim())) }
+    else { selection.labs.push(item) }
+  };
+  selection
+}
+
+/// The regex of a pattern of lab names: either a regex between
+/// slashes, which may match any part of the name (e.g.,
+/// `/mergesort[12]$/`), or a glob, which must match the whole name,
+/// where `*` matches any text and `?` matches any one character
+/// (e.g., `list-lazy-*`).  A name is a glob that matches only itself.
+pub fn regex_of_pattern(pattern:&str) -> Result<Regex, String> {
+  let re =
+    if pattern.len() > 1 && pattern.starts_with("/") && pattern.ends_with("/") {
+      String::from(&pattern[1..pattern.len() - 1])
+    } else {
+      format!("^{}$", ::regex::escape(pattern).replace("\\*", ".*").replace("\\?", "."))
+    };
+  Regex::new(&re).map_err(|e| format!("couldn't parse the pattern `{}`: {}", pattern, e))
+}
+
+/// Selects the labs of a catalog, in order: those that match a pattern
+/// of `labs` (if any), that have every tag of `tags`, and that match no
+/// pattern of `excludes`.  It is an error if a pattern of `labs`
+/// matches no lab of the catalog (e.g., a misspelled name), if no lab
+/// has a tag, or if we select no lab.
+pub fn select_labs(catalog:Vec<Box<Lab>>, selection:&LabSelection) -> Result<Vec<Box<Lab>>, String> {
+  let regexes = |patterns:&Vec<String>| -> Result<Vec<Regex>, String> {
+    patterns.iter().map(|p| regex_of_pattern(p)).collect()
+  };
+  let labs = regexes(&selection.labs)?;
+  let excludes = regexes(&selection.excludes)?;
+  let names : Vec<String> = catalog.iter().map(|lab| string_of_name( &lab.name() )).collect();
+  for (pattern, re) in selection.labs.iter().zip(labs.iter()) {
+    if !names.iter().any(|name| re.is_match(name)) {
+      return Err(format!("couldn't find the lab `{}`.", pattern))
+    }
+  };
+  for tag in selection.tags.iter() {
+    if !catalog.iter().any(|lab| lab.info().tags.contains(&tag.as_str())) {
+      return Err(format!("no lab has the tag `{}`.", tag))
+    }
+  };
+  let selected : Vec<_> = catalog.into_iter().zip(names.iter()).filter(|&(ref lab, name)| {
+    (labs.len() == 0 || labs.iter().any(|re| re.is_match(name))) &&
+      selection.tags.iter().all(|tag| lab.info().tags.contains(&tag.as_str())) &&
+      !excludes.iter().any(|re| re.is_match(name))
+  }).map(|(lab, _)| lab).collect();
+  if selected.len() == 0 {
+    return Err(String::from("no lab matches the selection."))
+  };
+  Ok(selected)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use adapton::engine::{Name,name_of_str};
+  use labdef::{LabInfo,LabTypes,LabParams,LabResults};
+
+  struct TestLab { name:&'static str, url:Option<String>, info:LabInfo, types:LabTypes }
+
+  impl Lab for TestLab {
+    fn name(self:&Self) -> Name { name_of_str(self.name) }
+    fn url(self:&Self) -> &Option<String> { &self.url }
+    fn info(self:&Self) -> &LabInfo { &self.info }
+    fn types(self:&Self) -> &LabTypes { &self.types }
+    fn run(self:&Self, _params:&LabParams) -> LabResults {
+      LabResults{ trajectories: vec![], failure: None }
+    }
+  }
+
+  fn catalog() -> Vec<Box<Lab>> {
+    let lab = |name, tags| -> Box<Lab> {
+      let types = LabTypes{ input:"", editst:"", output:"", editor:"", archivist:"" };
+      Box::new(TestLab{ name, url:None, info:LabInfo{ tags, ..LabInfo::default() }, types })
+    };
+    vec![lab("list-eager-map",    vec!["list", "eager"]),
+         lab("list-lazy-map",     vec!["list", "lazy"]),
+         lab("list-lazy-mergesort1", vec!["list", "lazy", "sort"]),
+         lab("list-lazy-mergesort2", vec!["list", "lazy", "sort"]),
+         lab("tree-max",          vec!["tree"])]
+  }
+
+  fn select(labs:&str, excludes:&str, tags:&str) -> Result<Vec<String>, String> {
+    let labs = select_labs(catalog(), &selection_of_lists(labs, excludes, tags))?;
+    Ok(labs.iter().map(|lab| string_of_name( &lab.name() )).collect())
+  }
+
+  #[test]
+  fn globs() {
+    assert_eq!(select("list-lazy-*", "", ""),
+               Ok(vec![String::from("list-lazy-map"), String::from("list-lazy-mergesort1"), String::from("list-lazy-mergesort2")]));
+    assert_eq!(select("list-lazy-mergesort?", "", ""),
+               Ok(vec![String::from("list-lazy-mergesort1"), String::from("list-lazy-mergesort2")]));
+    // A glob matches the whole name, and escapes the rest of the regex syntax
+    assert_eq!(select("map", "", ""), Err(String::from("couldn't find the lab `map`.")));
+    assert!(regex_of_pattern("list.eager.map").unwrap().is_match("list.eager.map"));
+    assert!(!regex_of_pattern("list.eager.map").unwrap().is_match("list-eager-map"));
+    assert_eq!(select("tree-max, list-eager-map", "", ""),
+               Ok(vec![String::from("list-eager-map"), String::from("tree-max")]));
+  }
+
+  #[test]
+  fn regexes() {
+    assert_eq!(select("/mergesort[12]$/", "", ""),
+               Ok(vec![String::from("list-lazy-mergesort1"), String::from("list-lazy-mergesort2")]));
+    assert_eq!(select("/^tree/,/eager/", "", ""),
+               Ok(vec![String::from("list-eager-map"), String::from("tree-max")]));
+  }
+
+  #[test]
+  fn bad_regex() {
+    let e = select("/mergesort[12/", "", "").unwrap_err();
+    assert!(e.starts_with("couldn't parse the pattern `/mergesort[12/`"), "{}", e);
+    assert!(select("", "/(/", "").unwrap_err().starts_with("couldn't parse the pattern `/(/`"));
+    assert!(select("!/(/", "", "").unwrap_err().starts_with("couldn't parse the pattern `/(/`"));
+  }
+
+  #[test]
+  fn exclusions() {
+    let lazy_maps = Ok(vec![String::from("list-lazy-map")]);
+    assert_eq!(select("list-lazy-*", "/mergesort/", ""), lazy_maps);
+    assert_eq!(select("list-lazy-*,!/mergesort/", "", ""), lazy_maps);
+    assert_eq!(select("!list-*", "", ""), Ok(vec![String::from("tree-max")]));
+    assert_eq!(select("", "list-*,tree-*", ""), Err(String::from("no lab matches the selection.")));
+  }
+
+  #[test]
+  fn tags() {
+    let sorts = Ok(vec![String::from("list-lazy-mergesort1"), String::from("list-lazy-mergesort2")]);
+    assert_eq!(select("", "", "lazy,sort"), sorts);
+    assert_eq!(select("tag:lazy,tag:sort", "", ""), sorts);
+    assert_eq!(select("tag:lazy", "", "sort"), sorts);
+    assert_eq!(select("tree-max,tag:list", "", ""), Err(String::from("no lab matches the selection.")));
+    assert_eq!(select("", "", "graph"), Err(String::from("no lab has the tag `graph`.")));
+  }
+
+  #[test]
+  fn selections_of_lists() {
+    let s = selection_of_lists(" a, !b ,tag:c,, ! d", "e", "f,g");
+    assert_eq!(s.labs, vec!["a"]);
+    assert_eq!(s.excludes, vec!["e", "b", "d"]);
+    assert_eq!(s.tags, vec!["f", "g", "c"]);
+    assert!(selection_of_lists("", "", "").is_all());
+    assert!(!selection_of_lists("!a", "", "").is_all());
+  }
+}
@@ -0,0 +1,298 @@
+use std::fs;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::fs::File;
+use std::str::FromStr;
+
+use adapton::reflect::string_of_name;
+use labdef::{LabParams,Lab,LabResults,string_of_nominal_strategy};
+use labrun::timing_stats;
+use labdata::{DataRow,DataVal,OutputFormat,write_data_rows,extension_of_format};
+
+/// The values of each swept parameter.  Each point of the sweep
+/// (See `sweep_points`) chooses one value for each; the other
+/// parameters are those of a base `LabParams`.
+#[derive(Clone,Debug)]
+pub struct SweepParams {
+  /// `GenerateParams::size`
+  pub sizes:       Vec<usize>,
+  /// `GenerateParams::gauge`
+  pub gauges:      Vec<usize>,
+  /// `SampleParams::change_batch_size`
+  pub batch_sizes: Vec<usize>,
+  /// `SampleParams::demand`
+  pub demands:     Vec<usize>,
+  /// `LabParams::change_batch_loopc`
+  pub loopcs:      Vec<usize>,
+}
+
+impl SweepParams {
+  /// True if some parameter has more than one value.
+  pub fn is_sweep(&self) -> bool {
+    self.sizes.len() > 1 || self.gauges.len() > 1 || self.batch_sizes.len() > 1 ||
+      self.demands.len() > 1 || self.loopcs.len() > 1
+  }
+}
+
+/// How to combine the values of the swept parameters into points.
+#[derive(Clone,Debug,PartialEq)]
+pub enum SweepMode {
+  /// Every combination of values (the cartesian product).
+  Product,
+  /// The `i`th point takes the `i`th value of each parameter; each
+  /// parameter has either one value (for every point), or one value
+  /// per point.
+  Zip,
+}
+
+/// Parses `product` or `zip`, e.g., from the command line.
+impl FromStr for SweepMode {
+  type Err = String;
+  fn from_str(s:&str) -> Result<SweepMode, String> {
+    match s {
+      "product" => Ok(SweepMode::Product),
+      "zip"     => Ok(SweepMode::Zip),
+      _ => Err(format!("unknown sweep mode `{}`; expected `product` or `zip`", s)),
+    }
+  }
+}
+
+/// The parameters of a point; the user's choices (See
+/// `LabParams::overrides`) take the values of the point too.
+fn with_point(base:&LabParams, size:usize, gauge:usize, batch_size:usize, demand:usize, loopc:usize) -> LabParams {
+  let mut p = base.clone();
+  p.sample_params.generate_params.size  = size;
+  p.sample_params.generate_params.gauge = gauge;
+  p.sample_params.change_batch_size     = batch_size;
+  p.sample_params.demand                = demand;
+  p.change_batch_loopc                  = loopc;
+  let o = &mut p.overrides;
+  o.size               = o.size.map(|_| size);
+  o.gauge              = o.gauge.map(|_| gauge);
+  o.change_batch_size  = o.change_batch_size.map(|_| batch_size);
+  o.demand             = o.demand.map(|_| demand);
+  o.change_batch_loopc = o.change_batch_loopc.map(|_| loopc);
+  p
+}
+
+/// The parameters of each point of a sweep, in order: For the
+/// product, the last parameter (`loopcs`) varies fastest.  To zip,
+/// each parameter needs either one value or as many as the others;
+/// otherwise, it is an error.
+pub fn sweep_points(base:&LabParams, sweep:&SweepParams, mode:&SweepMode) -> Result<Vec<LabParams>, String> {
+  let mut points = vec![];
+  match *mode {
+    SweepMode::Product => {
+      for &size in sweep.sizes.iter() {
+        for &gauge in sweep.gauges.iter() {
+          for &batch_size in sweep.batch_sizes.iter() {
+            for &demand in sweep.demands.iter() {
+              for &loopc in sweep.loopcs.iter() {
+                points.push(with_point(base, size, gauge, batch_size, demand, loopc))
+              }
+            }
+          }
+        }
+      }
+    },
+    SweepMode::Zip => {
+      let lists = [("size", &sweep.sizes), ("artfreq", &sweep.gauges), ("batch", &sweep.batch_sizes),
+                   ("demand", &sweep.demands), ("loopc", &sweep.loopcs)];
+      let n = lists.iter().map(|&(_, l)| l.len()).max().unwrap_or(0);
+      for &(name, l) in lists.iter() {
+        if l.len() != 1 && l.len() != n {
+          return Err(format!("to zip, each parameter needs either one value or {} values; --{} has {}.", n, name, l.len()))
+        }
+      };
+      let nth = |l:&Vec<usize>, i:usize| if l.len() == 1 { l[0] } else { l[i] };
+      for i in 0..n {
+        points.push(with_point(base, nth(&sweep.sizes, i), nth(&sweep.gauges, i), nth(&sweep.batch_sizes, i),
+                               nth(&sweep.demands, i), nth(&sweep.loopcs, i)))
+      }
+    },
+  };
+  Ok(points)
+}
+
+/// The results of one lab at one point of a sweep, summarized: The
+/// from-scratch times (of batch 0), and the median update times (of
+/// the later batches, whose DCG times measure change propagation),
+/// each for the Naive and DCG engines, in nanoseconds.
+#[derive(Clone,Debug)]
+pub struct SweepSummary {
+  /// The index of the point (See `sweep_points`).
+  pub point:            usize,
+  pub labname:          String,
+  pub params:           LabParams,
+  pub samples:          usize,
+  pub naive_initial_ns: Option<u64>,
+  pub dcg_initial_ns:   Option<u64>,
+  pub naive_update_ns:  Option<u64>,
+  pub dcg_update_ns:    Option<u64>,
+  /// Whether every sample's outputs agree; None without validation.
+  pub output_valid:     Option<bool>,
+  pub failed:           bool,
+}
+
+impl SweepSummary {
+  /// The speedup of the DCG over naive recomputation, for updates.
+  pub fn speedup(&self) -> Option<f64> {
+    match (self.naive_update_ns, self.dcg_update_ns) {
+      (Some(n), Some(d)) if d > 0 => Some((n as f64) / (d as f64)),
+      _ => None,
+    }
+  }
+}
+
+fn median(xs:&Vec<u64>) -> Option<u64> {
+  if xs.len() == 0 { None } else { Some(timing_stats(xs).median_ns) }
+}
+
+/// Summarizes the results of one lab at one point of a sweep.
+pub fn summarize_sweep_point(point:usize, params:&LabParams, lab:&Box<Lab>, results:&LabResults) -> SweepSummary {
+  let mut initial = (None, None);
+  let mut naive_updates = vec![];
+  let mut dcg_updates   = vec![];
+  let mut samples = 0;
+  let mut valid = true;
+  for trajectory in results.trajectories.iter() {
+    for sample in trajectory.samples.iter() {
+      samples += 1;
+      if sample.output_valid == Some(false) { valid = false };
+      let naive_ns = sample.naive_sample.compute_output.time_stats.median_ns;
+      let dcg_ns   = sample.dcg_sample.compute_output.time_stats.median_ns;
+      if sample.batch_name == 0 {
+        if initial.0.is_none() { initial = (Some(naive_ns), Some(dcg_ns)) }
+      } else {
+        naive_updates.push(naive_ns);
+        dcg_updates.push(dcg_ns);
+      }
+    }
+  };
+  SweepSummary{
+    point,
+    labname:          string_of_name( &lab.name() ),
+    params:           params.clone(),
+    samples,
+    naive_initial_ns: initial.0,
+    dcg_initial_ns:   initial.1,
+    naive_update_ns:  median(&naive_updates),
+    dcg_update_ns:    median(&dcg_updates),
+    output_valid:     if params.sample_params.validate_output { Some(valid) } else { None },
+    failed:           results.failures().len() > 0,
+  }
+}
+
+fn data_val_of_ns(ns:Option<u64>) -> DataVal {
+  match ns { Some(ns) => DataVal::Nat(ns), None => DataVal::Null }
+}
+
+/// Flattens a summary into one row, keyed by the point's index and
+/// its swept parameters, with the speedup of the DCG.
+pub fn data_row_of_sweep_summary(s:&SweepSummary) -> DataRow {
+  let sp = &s.params.sample_params;
+  let gp = &sp.generate_params;
+  let row : Vec<(&'static str, DataVal)> = vec![
+    ("point",              DataVal::Nat(s.point as u64)),
+    ("lab",                DataVal::Str(s.labname.clone())),
+    ("size",               DataVal::Nat(gp.size as u64)),
+    ("gauge",              DataVal::Nat(gp.gauge as u64)),
+    ("nominal_strategy",   DataVal::Str(String::from(string_of_nominal_strategy(&gp.nominal_strategy)))),
+    ("change_batch_size",  DataVal::Nat(sp.change_batch_size as u64)),
+    ("demand",             DataVal::Nat(sp.demand as u64)),
+    ("change_batch_loopc", DataVal::Nat(s.params.change_batch_loopc as u64)),
+    ("seeds",              DataVal::Nat(sp.input_seeds.len() as u64)),
+    ("samples",            DataVal::Nat(s.samples as u64)),
+    ("naive_initial_ns",   data_val_of_ns(s.naive_initial_ns)),
+    ("dcg_initial_ns",     data_val_of_ns(s.dcg_initial_ns)),
+    ("naive_update_ns",    data_val_of_ns(s.naive_update_ns)),
+    ("dcg_update_ns",      data_val_of_ns(s.dcg_update_ns)),
+    ("speedup",            match s.speedup() { Some(x) => DataVal::Num(x), None => DataVal::Null }),
+    ("output_valid",       match s.output_valid { Some(b) => DataVal::Bool(b), None => DataVal::Null }),
+    ("failed",             DataVal::Bool(s.failed)),
+  ];
+  row.into_iter().map(|(col, v)| (String::from(col), v)).collect()
+}
+
+/// Writes the summaries of a sweep to `<out>/sweep.<ext>`, one row
+/// each (See `data_row_of_sweep_summary`).
+pub fn write_sweep(out:&str, format:&OutputFormat, summaries:&Vec<SweepSummary>) {
+  let rows = summaries.iter().map(data_row_of_sweep_summary).collect();
+  fs::create_dir_all(out).unwrap();
+  let f = File::create(format!("{}/sweep.{}", out, extension_of_format(format))).unwrap();
+  let mut writer = BufWriter::new(f);
+  write_data_rows(&mut writer, format, &rows);
+  writer.flush().unwrap();
+}
+
+/// Writes the samples of every point of a sweep, together, to
+/// `<out>/samples.<ext>`, e.g., as a baseline (See `labcompare`).
+pub fn write_sweep_samples(out:&str, format:&OutputFormat, rows:&Vec<DataRow>) {
+  fs::create_dir_all(out).unwrap();
+  let f = File::create(format!("{}/samples.{}", out, extension_of_format(format))).unwrap();
+  let mut writer = BufWriter::new(f);
+  write_data_rows(&mut writer, format, rows);
+  writer.flush().unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use labdef::{NominalStrategy,ParamsOverride};
+  use labmain::lab_params_defaults;
+
+  fn sweep(sizes:Vec<usize>, gauges:Vec<usize>, batch_sizes:Vec<usize>, demands:Vec<usize>, loopcs:Vec<usize>) -> SweepParams {
+    SweepParams{ sizes, gauges, batch_sizes, demands, loopcs }
+  }
+
+  /// The swept fields of a point: size, gauge, batch size, demand and loopc.
+  fn point(p:&LabParams) -> (usize, usize, usize, usize, usize) {
+    (p.sample_params.generate_params.size, p.sample_params.generate_params.gauge,
+     p.sample_params.change_batch_size, p.sample_params.demand, p.change_batch_loopc)
+  }
+
+  #[test]
+  fn product_size_and_order() {
+    let s = sweep(vec![10, 20], vec![1], vec![1, 2, 4], vec![5], vec![3, 6]);
+    let points = sweep_points(&lab_params_defaults(), &s, &SweepMode::Product).unwrap();
+    assert_eq!(points.len(), 2 * 1 * 3 * 1 * 2);
+    // The last parameter varies fastest
+    assert_eq!(points.iter().map(point).collect::<Vec<_>>(),
+               vec![(10, 1, 1, 5, 3), (10, 1, 1, 5, 6), (10, 1, 2, 5, 3), (10, 1, 2, 5, 6),
+                    (10, 1, 4, 5, 3), (10, 1, 4, 5, 6), (20, 1, 1, 5, 3), (20, 1, 1, 5, 6),
+                    (20, 1, 2, 5, 3), (20, 1, 2, 5, 6), (20, 1, 4, 5, 3), (20, 1, 4, 5, 6)]);
+  }
+
+  #[test]
+  fn zip_size_and_order() {
+    let s = sweep(vec![10, 20, 30], vec![1], vec![1, 2, 4], vec![5], vec![3]);
+    let points = sweep_points(&lab_params_defaults(), &s, &SweepMode::Zip).unwrap();
+    assert_eq!(points.iter().map(point).collect::<Vec<_>>(),
+               vec![(10, 1, 1, 5, 3), (20, 1, 2, 5, 3), (30, 1, 4, 5, 3)]);
+    let s = sweep(vec![10, 20, 30], vec![1], vec![1, 2], vec![5], vec![3]);
+    assert_eq!(sweep_points(&lab_params_defaults(), &s, &SweepMode::Zip).map(|ps| ps.len()),
+               Err(String::from("to zip, each parameter needs either one value or 3 values; --batch has 2.")));
+  }
+
+  #[test]
+  fn with_point_sets_only_the_swept_fields() {
+    let mut base = lab_params_defaults();
+    base.sample_params.input_seeds = vec![1, 2, 3];
+    base.sample_params.generate_params.nominal_strategy = NominalStrategy::ByContent;
+    base.sample_params.timing_reps = 7;
+    base.output_dir = String::from("sweep-results");
+    base.overrides = ParamsOverride{ size:Some(8), demand:Some(6), ..ParamsOverride::default() };
+    let p = with_point(&base, 100, 2, 4, 9, 5);
+    assert_eq!(point(&p), (100, 2, 4, 9, 5));
+    // The user's choices take the values of the point; the others stay unchosen
+    assert_eq!(p.overrides, ParamsOverride{ size:Some(100), demand:Some(9), ..ParamsOverride::default() });
+    // The other fields are those of the base
+    assert_eq!(p.sample_params.input_seeds, vec![1, 2, 3]);
+    assert_eq!(p.sample_params.generate_params.nominal_strategy, NominalStrategy::ByContent);
+    assert_eq!(p.sample_params.timing_reps, 7);
+    assert_eq!(p.sample_params.validate_output, base.sample_params.validate_output);
+    assert_eq!(p.sample_params.reflect_dcg, base.sample_params.reflect_dcg);
+    assert_eq!(p.output_dir, "sweep-results");
+    assert!(p.replay_edits.is_none() && p.preset.is_none());
+  }
+}
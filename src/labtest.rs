@@ -0,0 +1,186 @@
+use adapton::reflect::string_of_name;
+use labdef::{Lab,LabParams,lab_params};
+use labmain::lab_params_defaults;
+
+/// The parameters of a consistency test (See `check_consistency`):
+/// one trajectory per input seed, each with `loopc` change batches
+/// after the initial run, validating every output.  We neither
+/// reflect nor count effects, and we time each sample once; the
+/// other parameters are the defaults (See `lab_params_defaults`),
+/// or those of the lab (See `labdef::lab_params`).
+pub fn consistency_params(seeds:Vec<usize>, loopc:usize) -> LabParams {
+  let mut params = lab_params_defaults();
+  params.sample_params.input_seeds     = seeds;
+  params.sample_params.validate_output = true;
+  params.sample_params.reflect_dcg     = false;
+  params.sample_params.reflect_trace   = false;
+  params.sample_params.count_effects   = false;
+  params.sample_params.timing_warmup   = 0;
+  params.sample_params.timing_reps     = 1;
+  params.change_batch_loopc            = loopc;
+  params.overrides.change_batch_loopc  = Some(loopc);
+  params
+}
+
+/// Runs a lab, and checks its from-scratch consistency: that the DCG
+/// and naive outputs agree for every sample.  On the first sample
+/// whose outputs disagree, the error gives its input seed, its batch
+/// and the `Debug` text of both outputs; on a failure of the lab
+/// (e.g., a panic), the error gives the failure.
+pub fn check_consistency(lab:&Box<Lab>, params:&LabParams) -> Result<(), String> {
+  let labname = string_of_name( &lab.name() );
+  let mut params = lab_params(params, lab.info());
+  params.sample_params.validate_output = true;
+  let results = lab.run(&params);
+  match results.failures().first() {
+    None => (),
+    Some(f) => return Err(format!("lab `{}` failed: input seed {:?}, batch {:?}, engine {:?}: {}",
+                                  labname, f.seed, f.batch_name, f.engine, f.message)),
+  };
+  for trajectory in results.trajectories.iter() {
+    for sample in trajectory.samples.iter() {
+      match sample.output_mismatch {
+        None => (),
+        Some((ref dcg, ref naive)) =>
+          return Err(format!("lab `{}` is not from-scratch consistent: input seed {}, batch {}:\n  DCG output:   {}\n  naive output: {}",
+                             labname, trajectory.seed, sample.batch_name, dcg, naive)),
+      }
+    }
+  };
+  Ok(())
+}
+
+/// Like `check_consistency`, but panics on an error, e.g., for a test.
+pub fn assert_consistent(lab:&Box<Lab>, params:&LabParams) {
+  match check_consistency(lab, params) {
+    Ok(()) => (),
+    Err(e) => panic!("{}", e),
+  }
+}
+
+/// Defines a test that checks the from-scratch consistency of a lab
+/// (See `check_consistency`), given the name of the test and the lab
+/// (e.g., from `labdef!`), and optionally, the input seeds (e.g.,
+/// `0..20`) and the number of change batches, and then the nominal
+/// strategy; by default, ten seeds, ten batches and
+/// `NominalStrategy::Regular`.  For instance:
+///
+/// ```text
+/// lab_test!(list_tree_max_is_consistent,
+///           labdef!(name_of_str("list-tree-max"), None,
+///                   List<usize>, usize, usize,
+///                   UniformPrepend<_,_>, ListTreeMax),
+///           0..20, 50, NominalStrategy::ByContent);
+/// ```
+#[macro_export]
+macro_rules! lab_test {
+  ( $test:ident, $lab:expr ) => {
+    lab_test!($test, $lab, 0..10, 10);
+  };
+  ( $test:ident, $lab:expr, $seeds:expr, $loopc:expr ) => {
+    lab_test!($test, $lab, $seeds, $loopc, $crate::labdef::NominalStrategy::Regular);
+  };
+  ( $test:ident, $lab:expr, $seeds:expr, $loopc:expr, $nominal:expr ) => {
+    #[test]
+    fn $test() {
+      let lab : Box<$crate::labdef::Lab> = $lab;
+      let seeds = ::std::iter::IntoIterator::into_iter($seeds).collect();
+      let mut params = $crate::labtest::consistency_params(seeds, $loopc);
+      params.sample_params.generate_params.nominal_strategy = $nominal;
+      $crate::labtest::assert_consistent(&lab, &params)
+    }
+  };
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use adapton::engine::{Name,name_of_str};
+  use labdef::*;
+  use labrun::timing_stats;
+
+  /// A lab whose DCG output disagrees with its naive output for input
+  /// seed 3, from batch 2 on, but only when naming by content.
+  struct InconsistentLab { info:LabInfo, types:LabTypes }
+
+  impl Lab for InconsistentLab {
+    fn name(self:&Self) -> Name { name_of_str("inconsistent") }
+    fn url(self:&Self) -> &Option<String> { &None }
+    fn info(self:&Self) -> &LabInfo { &self.info }
+    fn types(self:&Self) -> &LabTypes { &self.types }
+    fn run(self:&Self, params:&LabParams) -> LabResults {
+      let by_content = params.sample_params.generate_params.nominal_strategy == NominalStrategy::ByContent;
+      let trajectories = params.sample_params.input_seeds.iter().map(|&seed| {
+        let samples = (0..params.change_batch_loopc + 1).map(|b| {
+          let mismatch = if by_content && seed == 3 && b >= 2 {
+            Some((format!("[{}]", b), format!("[{}]", b + 1)))
+          } else { None };
+          Sample{ batch_name: b, dcg_sample: engine_sample(), naive_sample: engine_sample(),
+                  output_valid: Some(mismatch.is_none()), output_mismatch: mismatch }
+        }).collect();
+        Trajectory{ seed, samples, failure: None, edit_script: None }
+      }).collect();
+      LabResults{ trajectories, failure: None }
+    }
+  }
+
+  fn engine_sample() -> EngineSample {
+    let metrics = EngineMetrics{
+      time_ns: 0, time_ns_samples: vec![], time_stats: timing_stats(&[]), batch_times_ns: vec![],
+      effect_counts: EffectCounts::default(), reflect_traces: vec![], reflect_dcg: None,
+    };
+    EngineSample{ process_input: metrics.clone(), compute_output: metrics, input: None, output: None }
+  }
+
+  fn inconsistent_lab() -> Box<Lab> {
+    let types = LabTypes{ input:"", editst:"", output:"", editor:"", archivist:"" };
+    Box::new(InconsistentLab{ info:LabInfo::default(), types })
+  }
+
+  fn by_content(mut params:LabParams) -> LabParams {
+    params.sample_params.generate_params.nominal_strategy = NominalStrategy::ByContent;
+    params
+  }
+
+  #[test]
+  fn mismatch_gives_the_failing_seed_and_batch() {
+    let params = by_content(consistency_params((0..10).collect(), 10));
+    assert_eq!(check_consistency(&inconsistent_lab(), &params),
+               Err(String::from("lab `inconsistent` is not from-scratch consistent: input seed 3, batch 2:\n  \
+                                 DCG output:   [2]\n  naive output: [3]")));
+    // Without the failing seed, or before the failing batch, it is consistent
+    assert_eq!(check_consistency(&inconsistent_lab(), &by_content(consistency_params(vec![0, 1, 2, 4], 10))), Ok(()));
+    assert_eq!(check_consistency(&inconsistent_lab(), &by_content(consistency_params(vec![3], 1))), Ok(()));
+  }
+
+  #[test]
+  fn lab_failure_gives_the_failing_seed_and_batch() {
+    struct PanickedLab { info:LabInfo, types:LabTypes }
+    impl Lab for PanickedLab {
+      fn name(self:&Self) -> Name { name_of_str("panicked") }
+      fn url(self:&Self) -> &Option<String> { &None }
+      fn info(self:&Self) -> &LabInfo { &self.info }
+      fn types(self:&Self) -> &LabTypes { &self.types }
+      fn run(self:&Self, _params:&LabParams) -> LabResults {
+        let failure = LabFailure{ message: String::from("oops"), engine: Some(EngineKind::DCG),
+                                  seed: Some(5), batch_name: Some(4) };
+        LabResults{ trajectories: vec![], failure: Some(failure) }
+      }
+    }
+    let types = LabTypes{ input:"", editst:"", output:"", editor:"", archivist:"" };
+    let lab : Box<Lab> = Box::new(PanickedLab{ info:LabInfo::default(), types });
+    assert_eq!(check_consistency(&lab, &consistency_params(vec![5], 10)),
+               Err(String::from("lab `panicked` failed: input seed Some(5), batch Some(4), engine Some(DCG): oops")));
+  }
+
+  #[test]
+  #[should_panic(expected = "input seed 3, batch 2:")]
+  fn assert_consistent_panics_on_a_mismatch() {
+    assert_consistent(&inconsistent_lab(), &by_content(consistency_params((0..10).collect(), 10)))
+  }
+
+  // The lab is consistent with the default, regular naming
+  lab_test!(inconsistent_lab_is_consistent_by_default, inconsistent_lab());
+  lab_test!(inconsistent_lab_is_consistent_before_batch_2, inconsistent_lab(), 3..4, 1,
+            NominalStrategy::ByContent);
+}
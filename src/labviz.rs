@@ -4,11 +4,17 @@ use std::io::prelude::*;
 use std::io::BufWriter;
 use std::fs::File;
 use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 use adapton::engine::Name;
 use adapton::reflect::*;
 //use adapton::engine::reflect::{trace, string_of_name, string_of_loc};
-use labdef::{LabParams,Lab,LabResults, Sample};
+use labdef::{LabParams,Lab,LabResults, Sample, TimingStats, EffectCounts, LabFailure};
+use labdata::{fields_of_effect_counts, LabSummary, summarize_lab_results};
+use labdiff::{DcgDiff, diff_dcg};
+use labrun::timing_stats;
+use labplot::{Chart, Series, write_svg_chart, NAIVE_COLOR, DCG_COLOR, PALETTE};
+use labsweep::SweepSummary;
 
 /// The `Div` struct represents a restricted form of a `<div>` element
 /// in HTML.  The field `tag` is a string, which corresponds to a
@@ -360,6 +366,7 @@ pub fn div_of_trace (tr:&trace::Trace) -> Div {
           trace::Effect::Force(trace::ForceCase::CompCacheMiss)  => "tr-force-compcache-miss",
           trace::Effect::Force(trace::ForceCase::CompCacheHit)   => "tr-force-compcache-hit",
           trace::Effect::Force(trace::ForceCase::RefGet)         => "tr-force-refget",
+          trace::Effect::Debug(_,_)                              => "tr-debug",
         })
       ],
       extent: Box::new(
@@ -380,6 +387,7 @@ pub fn div_of_trace (tr:&trace::Trace) -> Div {
                 trace::Effect::Force(trace::ForceCase::CompCacheMiss)  => "Force(CompCacheMiss)",
                 trace::Effect::Force(trace::ForceCase::CompCacheHit)   => "Force(CompCacheHit)",
                 trace::Effect::Force(trace::ForceCase::RefGet)         => "Force(RefGet)",
+                trace::Effect::Debug(_,_)                              => "Debug",
               })),
             classes: vec![],
             extent: Box::new(vec![]),
@@ -460,13 +468,13 @@ impl<T:WriteHTML> WriteHTML for Vec<T> {
 }
 
 pub fn write_lab_results_summary
-  (_params:&LabParams, 
+  (params:&LabParams, 
    labs:&Vec<Box<Lab>>, 
    results:&Vec<LabResults>) 
 {
   // Create directories and files on local filesystem:
-  fs::create_dir_all("lab-results").unwrap();
-  let f = File::create(format!("lab-results/index.html")).unwrap();
+  fs::create_dir_all(&params.output_dir).unwrap();
+  let f = File::create(format!("{}/index.html", params.output_dir)).unwrap();
   let mut writer = BufWriter::new(f);
 
   writeln!(writer, "{}", style_string()).unwrap();
@@ -476,24 +484,329 @@ pub fn write_lab_results_summary
 
   writeln!(writer, "<div class={:?}>Lab results summary</div>", "labsum-title").unwrap();
 
-  for ((_i,lab),(_j,_result)) in 
+  // List the failed labs first, so they are hard to miss.
+  let failed : Vec<_> = labs.iter().zip(results.iter()).filter(|&(_, r)| r.failures().len() > 0).collect();
+  if failed.len() > 0 {
+    writeln!(writer, "<div class={:?}>", "labsum-failures").unwrap();
+    writeln!(writer, "<div class=\"label\">Failed labs ({}):</div>", failed.len()).unwrap();
+    for &(lab, result) in failed.iter() {
+      writeln!(writer, "<a class={:?} href=./{}/index.html>{}</a>",
+               "labsum-failed-lab", string_of_name(&lab.name()), string_of_name(&lab.name())).unwrap();
+      for failure in result.failures() {
+        write_lab_failure(&mut writer, failure);
+      }
+    };
+    writeln!(writer, "</div>").unwrap();
+    write_cr(&mut writer);
+  };
+
+  let summaries = labs.iter().zip(results.iter()).map(|(lab, result)| summarize_lab_results(lab, result)).collect();
+  write_lab_summary_table(&mut writer, &summaries);
+  write_cr(&mut writer);
+
+  for ((_i,lab),(_j,result)) in 
     labs.iter().enumerate().zip(results.iter().enumerate()) 
   {
     writeln!(&mut writer, "<div class={:?}>", "labsum-row").unwrap();
     writeln!(&mut writer, "<div class={:?}>", "labsum-name").unwrap();
     write_lab_name(&mut writer, lab, false);
+    write_lab_info(&mut writer, lab);
     writeln!(&mut writer, "</div>").unwrap();
     
     writeln!(&mut writer, "<a class={:?} href=./{}/index.html>detailed results</a>", 
              "lab-details", 
              string_of_name(&lab.name())
     ).unwrap();
+    if result.failures().len() > 0 {
+      writeln!(&mut writer, "<div class={:?}>failed</div>", "labsum-failed").unwrap();
+    }
+    write_lab_charts(&mut writer, result);
 
     writeln!(&mut writer, "</div>").unwrap();        
     write_cr(&mut writer);
   }
 }
 
+/// For each batch, the median compute times (in milliseconds) of the
+/// Naive and DCG engines, over the trajectories of the results.
+pub fn compute_times_per_batch(results:&LabResults) -> Vec<(usize, f64, f64)> {
+  let mut batches : BTreeMap<usize, (Vec<u64>, Vec<u64>)> = BTreeMap::new();
+  for trajectory in results.trajectories.iter() {
+    for sample in trajectory.samples.iter() {
+      let times = batches.entry(sample.batch_name).or_insert((vec![], vec![]));
+      times.0.push(sample.naive_sample.compute_output.time_stats.median_ns);
+      times.1.push(sample.dcg_sample.compute_output.time_stats.median_ns);
+    }
+  };
+  batches.iter().map(|(&b, &(ref naive, ref dcg))|
+                     (b, timing_stats(naive).median_ns as f64 / 1e6, timing_stats(dcg).median_ns as f64 / 1e6)).collect()
+}
+
+/// Writes charts of a lab's compute times, for the Naive and DCG
+/// engines: per batch, and cumulative (See `compute_times_per_batch`).
+/// Batch 0 computes from scratch, and later batches update; the DCG
+/// wins where its line falls below the Naive one.
+pub fn write_lab_charts<W:Write>(writer:&mut W, results:&LabResults) {
+  let times = compute_times_per_batch(results);
+  let mut cumulative = vec![];
+  let (mut naive_sum, mut dcg_sum) = (0.0, 0.0);
+  for &(b, naive, dcg) in times.iter() {
+    naive_sum += naive; dcg_sum += dcg;
+    cumulative.push((b, naive_sum, dcg_sum))
+  };
+  let series = |ts:&Vec<(usize, f64, f64)>| vec![
+    Series{ label:String::from("Naive"), color:NAIVE_COLOR, points:ts.iter().map(|&(b, n, _)| (b as f64, n)).collect() },
+    Series{ label:String::from("DCG"),   color:DCG_COLOR,   points:ts.iter().map(|&(b, _, d)| (b as f64, d)).collect() },
+  ];
+  writeln!(writer, "<div class={:?}>", "labsum-charts").unwrap();
+  write_svg_chart(writer, &Chart{
+    title:String::from("Compute time per batch"), x_label:String::from("batch"), y_label:String::from("ms"),
+    log_x:false, hline:None, series:series(&times),
+  });
+  write_svg_chart(writer, &Chart{
+    title:String::from("Cumulative compute time"), x_label:String::from("batch"), y_label:String::from("ms"),
+    log_x:false, hline:None, series:series(&cumulative),
+  });
+  writeln!(writer, "</div>").unwrap();
+}
+
+/// Labels a sweep point by the parameters (other than the input
+/// size) that vary across the sweep.
+fn label_of_sweep_point(s:&SweepSummary, varying:&Vec<(&'static str, fn(&SweepSummary) -> usize)>) -> String {
+  let parts : Vec<String> = varying.iter().map(|&(name, param)| format!("{} {}", name, param(s))).collect();
+  if parts.len() == 0 { String::from("DCG speedup") } else { parts.join(", ") }
+}
+
+/// Writes the summary of a sweep (See `labsweep`) to
+/// `<out>/index.html`: For each lab, a chart of the speedup of the DCG
+/// over naive recomputation versus input size, with one line for each
+/// combination of the other swept parameters.
+pub fn write_sweep_results_summary(out:&str, summaries:&Vec<SweepSummary>) {
+  fs::create_dir_all(out).unwrap();
+  let f = File::create(format!("{}/index.html", out)).unwrap();
+  let mut writer = BufWriter::new(f);
+
+  writeln!(writer, "{}", style_string()).unwrap();
+  writeln!(writer, "<style> .tool-label-toggles {{ display: none }} </style>").unwrap();
+  writeln!(writer, "<div class={:?}>Lab sweep summary</div>", "labsum-title").unwrap();
+
+  let params : Vec<(&'static str, fn(&SweepSummary) -> usize)> = vec![
+    ("artfreq", |s| s.params.sample_params.generate_params.gauge),
+    ("batch",   |s| s.params.sample_params.change_batch_size),
+    ("demand",  |s| s.params.sample_params.demand),
+    ("loopc",   |s| s.params.change_batch_loopc),
+  ];
+  let varying : Vec<_> = params.into_iter().filter(
+    |&(_, param)| summaries.iter().any(|s| param(s) != param(&summaries[0]))
+  ).collect();
+
+  let mut labnames : Vec<&String> = vec![];
+  for s in summaries.iter() {
+    if !labnames.contains(&&s.labname) { labnames.push(&s.labname) }
+  };
+  for labname in labnames.iter() {
+    // One series per combination of the other parameters, ordered by input size
+    let mut series : Vec<Series> = vec![];
+    for s in summaries.iter().filter(|s| s.labname == **labname) {
+      let label = label_of_sweep_point(s, &varying);
+      let size  = s.params.sample_params.generate_params.size as f64;
+      let point = match s.speedup() { Some(x) => (size, x), None => continue };
+      match series.iter().position(|ser| ser.label == label) {
+        Some(i) => series[i].points.push(point),
+        None => {
+          let color = PALETTE[series.len() % PALETTE.len()];
+          series.push(Series{ label, color, points:vec![point] })
+        }
+      }
+    };
+    for ser in series.iter_mut() {
+      ser.points.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap())
+    };
+    let sizes : Vec<f64> = series.iter().flat_map(|ser| ser.points.iter().map(|p| p.0)).collect();
+    let log_x = sizes.len() > 0 && sizes.iter().all(|&x| x > 0.0) &&
+      sizes.iter().fold(0.0, |a:f64, &x| a.max(x)) >= 10.0 * sizes.iter().fold(::std::f64::INFINITY, |a:f64, &x| a.min(x));
+    writeln!(writer, "<div class={:?}>", "labsum-row").unwrap();
+    writeln!(writer, "<div class={:?}>{}</div>", "labsum-name", labname).unwrap();
+    if summaries.iter().any(|s| s.labname == **labname && s.failed) {
+      writeln!(writer, "<div class={:?}>failed</div>", "labsum-failed").unwrap();
+    }
+    writeln!(writer, "<div class={:?}>", "labsum-charts").unwrap();
+    write_svg_chart(&mut writer, &Chart{
+      title:String::from("DCG speedup vs input size"), x_label:String::from("input size"),
+      y_label:String::from("speedup (naive / DCG)"), log_x, hline:Some(1.0), series,
+    });
+    writeln!(writer, "</div>").unwrap();
+    writeln!(writer, "</div>").unwrap();
+    write_cr(&mut writer);
+  }
+}
+
+/// The name of the file that describes a run, in its directory (See
+/// `write_runs_index`).
+pub const RUN_INFO_FILE : &'static str = "run.txt";
+
+/// Writes `<out>/runs.html`, an index of the runs in the
+/// subdirectories of `out` (e.g., named or timestamped runs), newest
+/// first: for each, a link to its summary page, its
+/// command line, and its tables.  A run is a subdirectory with a file
+/// `RUN_INFO_FILE`, whose lines each consist of a key (`command` or
+/// `params`) and a value, separated by a tab.
+pub fn write_runs_index(out:&str) {
+  let mut runs = vec![];
+  for entry in fs::read_dir(out).unwrap() {
+    let path = entry.unwrap().path();
+    let info = path.join(RUN_INFO_FILE);
+    if !info.exists() { continue };
+    let mut text = String::new();
+    File::open(&info).unwrap().read_to_string(&mut text).unwrap();
+    let field = |key:&str| text.lines().filter_map(|l| {
+      let mut kv = l.splitn(2, '\t');
+      if kv.next() == Some(key) { kv.next().map(String::from) } else { None }
+    }).next().unwrap_or(String::new());
+    let name = path.file_name().unwrap().to_string_lossy().into_owned();
+    let tables : Vec<String> = ["summary", "sweep", "samples", "regressions"].iter().flat_map(|t| {
+      ["csv", "tsv", "json"].iter().map(move |ext| format!("{}.{}", t, ext))
+    }).filter(|f| path.join(f).exists()).collect();
+    let modified = fs::metadata(&info).and_then(|m| m.modified()).ok();
+    runs.push((modified, name, field("command"), field("params"), path.join("index.html").exists(), tables));
+  };
+  runs.sort_by(|a, b| (&b.0, &b.1).cmp(&(&a.0, &a.1)));
+
+  let f = File::create(format!("{}/runs.html", out)).unwrap();
+  let mut writer = BufWriter::new(f);
+  writeln!(writer, "{}", style_string()).unwrap();
+  writeln!(writer, "<style> .tool-label-toggles {{ display: none }} </style>").unwrap();
+  writeln!(writer, "<div class={:?}>Lab runs</div>", "labsum-title").unwrap();
+  writeln!(writer, "<table class={:?} id={:?}>", "labsum-table", "runs-table").unwrap();
+  writeln!(writer, "<tr>").unwrap();
+  for (i, h) in ["run", "command", "tables"].iter().enumerate() {
+    writeln!(writer, "<th onclick=\"sortTable('runs-table', {})\">{}</th>", i, h).unwrap();
+  };
+  writeln!(writer, "</tr>").unwrap();
+  for &(_, ref name, ref command, ref params, has_index, ref tables) in runs.iter() {
+    writeln!(writer, "<tr>").unwrap();
+    if has_index {
+      writeln!(writer, "<td data-sort={:?}><a href=./{}/index.html>{}</a></td>", name, name, escape_html(name)).unwrap();
+    } else {
+      writeln!(writer, "<td data-sort={:?}>{}</td>", name, escape_html(name)).unwrap();
+    };
+    writeln!(writer, "<td data-sort=\"{}\" title=\"{}\"><code>{}</code></td>", escape_html(command), escape_html(params), escape_html(command)).unwrap();
+    let links : Vec<String> = tables.iter().map(|t| format!("<a href=./{}/{}>{}</a>", name, t, t)).collect();
+    writeln!(writer, "<td data-sort=\"\">{}</td>", links.join(" ")).unwrap();
+    writeln!(writer, "</tr>").unwrap();
+  };
+  writeln!(writer, "</table>").unwrap();
+}
+
+/// Writes a table cell for a time, in milliseconds, or for a ratio;
+/// the attribute `data-sort` holds its value, for sorting (See
+/// `sortTable` in `style_string`).
+fn write_summary_cell<W:Write>(writer:&mut W, x:Option<f64>, scale:f64, digits:usize) {
+  match x {
+    None    => writeln!(writer, "<td data-sort=\"\">-</td>").unwrap(),
+    Some(x) => writeln!(writer, "<td data-sort=\"{}\">{:.*}</td>", x, digits, x / scale).unwrap(),
+  }
+}
+
+/// Writes a table with one row per lab (See `LabSummary`): times of
+/// the initial run and of updates, the speedup of the DCG and the
+/// overhead of its initial run, and the validity of the outputs.
+/// Clicking a column header sorts the table by that column.
+pub fn write_lab_summary_table<W:Write>(writer:&mut W, summaries:&Vec<LabSummary>) {
+  let headers = ["lab", "samples",
+                 "naive initial (ms)", "DCG initial (ms)", "naive update (ms)", "DCG update (ms)",
+                 "speedup (naive/DCG)", "overhead (DCG/naive)", "validated", "mismatches"];
+  writeln!(writer, "<table class={:?} id={:?}>", "labsum-table", "labsum-table").unwrap();
+  writeln!(writer, "<tr>").unwrap();
+  for (i, h) in headers.iter().enumerate() {
+    writeln!(writer, "<th onclick=\"sortTable('labsum-table', {})\">{}</th>", i, h).unwrap();
+  };
+  writeln!(writer, "</tr>").unwrap();
+  for s in summaries.iter() {
+    let class = if s.failed || s.mismatches > 0 { "labsum-table-bad" } else { "labsum-table-ok" };
+    writeln!(writer, "<tr class={:?}>", class).unwrap();
+    writeln!(writer, "<td data-sort={:?}><a href=./{}/index.html>{}</a></td>", s.labname, s.labname, s.labname).unwrap();
+    writeln!(writer, "<td data-sort=\"{}\">{}</td>", s.samples, s.samples).unwrap();
+    write_summary_cell(writer, s.naive_initial_ns, 1e6, 3);
+    write_summary_cell(writer, s.dcg_initial_ns,   1e6, 3);
+    write_summary_cell(writer, s.naive_update_ns,  1e6, 3);
+    write_summary_cell(writer, s.dcg_update_ns,    1e6, 3);
+    write_summary_cell(writer, s.speedup,  1.0, 2);
+    write_summary_cell(writer, s.overhead, 1.0, 2);
+    writeln!(writer, "<td data-sort=\"{}\">{}</td>", s.validated, s.validated).unwrap();
+    writeln!(writer, "<td data-sort=\"{}\">{}</td>", s.mismatches, s.mismatches).unwrap();
+    writeln!(writer, "</tr>").unwrap();
+  };
+  writeln!(writer, "</table>").unwrap();
+}
+
+/// Writes a lab failure: where the lab failed, and its message.
+pub fn write_lab_failure<W:Write>(writer:&mut W, failure:&LabFailure) {
+  writeln!(writer, "<div class=\"lab-failure\">failure in input seed {}, batch {}, engine {}: <div class=\"lab-failure-msg\">{}</div></div>",
+           match failure.seed { Some(s) => format!("{}", s), None => String::from("(none)") },
+           match failure.batch_name { Some(b) => format!("{}", b), None => String::from("(none)") },
+           match failure.engine { Some(ref e) => format!("{:?}", e), None => String::from("(none)") },
+           escape_html(&failure.message)).unwrap();
+}
+
+/// Writes the summary statistics of a sample's timed runs.
+pub fn write_timing_stats<W:Write>(writer:&mut W, stats:&TimingStats) {
+  writeln!(writer, "<div class=\"time-stats\">n={} min={} median={} mean={:.0} stddev={:.0} p95={} (ns)</div>",
+           stats.count, stats.min_ns, stats.median_ns, stats.mean_ns, stats.stddev_ns, stats.p95_ns).unwrap();
+}
+
+/// Writes the nonzero trace effect counts of a sample.
+pub fn write_effect_counts<W:Write>(writer:&mut W, label:&str, counts:&EffectCounts) {
+  writeln!(writer, "<div class=\"effect-counts\"><div class=\"label\">{}</div>", label).unwrap();
+  for &(name, n) in fields_of_effect_counts(counts).iter() {
+    if n > 0 {
+      writeln!(writer, "<div class=\"effect-count-lab\">{}: <div class=\"effect-count\">{}</div></div>",
+               name, n).unwrap();
+    }
+  };
+  writeln!(writer, "</div>").unwrap();
+}
+
+fn write_dcg_diff_locs<W:Write>(writer:&mut W, class:&str, label:&str, locs:&Vec<Loc>) {
+  if locs.len() == 0 { return };
+  writeln!(writer, "<div class=\"dcg-diff-part {}\"><div class=\"dcg-diff-lab\">{} ({})</div>",
+           class, label, locs.len()).unwrap();
+  for loc in locs.iter() {
+    writeln!(writer, "<div class=\"dcg-diff-loc\">{}</div>", string_of_loc(loc)).unwrap();
+  };
+  writeln!(writer, "</div>").unwrap();
+}
+
+fn write_dcg_diff_edges<W:Write>(writer:&mut W, class:&str, label:&str, edges:&Vec<(Loc, Succ)>) {
+  if edges.len() == 0 { return };
+  writeln!(writer, "<div class=\"dcg-diff-part {}\"><div class=\"dcg-diff-lab\">{} ({})</div>",
+           class, label, edges.len()).unwrap();
+  for &(ref src, ref succ) in edges.iter() {
+    writeln!(writer, "<div class=\"dcg-diff-loc\">{} &rarr; {} ({})</div>",
+             string_of_loc(src), string_of_loc(&succ.loc),
+             match succ.effect { Effect::Force => "force", Effect::Alloc => "alloc" }).unwrap();
+  };
+  writeln!(writer, "</div>").unwrap();
+}
+
+/// Writes a structural diff of two DCGs (See `labdiff::diff_dcg`).
+pub fn write_dcg_diff<W:Write>(writer:&mut W, label:&str, diff:&DcgDiff) {
+  writeln!(writer, "<div class=\"dcg-diff\">").unwrap();
+  writeln!(writer, "<div class=\"label\">{}</div>", label).unwrap();
+  if diff.is_empty() {
+    writeln!(writer, "<div class=\"dcg-diff-lab\">no change</div>").unwrap();
+  };
+  write_dcg_diff_locs(writer,  "dcg-diff-nodes-added",      "nodes added",      &diff.nodes_added);
+  write_dcg_diff_locs(writer,  "dcg-diff-nodes-removed",    "nodes removed",    &diff.nodes_removed);
+  write_dcg_diff_locs(writer,  "dcg-diff-nodes-reevaluated","nodes re-evaluated", &diff.nodes_reevaluated);
+  write_dcg_diff_locs(writer,  "dcg-diff-values-changed",   "values changed",   &diff.values_changed);
+  write_dcg_diff_edges(writer, "dcg-diff-edges-added",      "edges added",      &diff.edges_added);
+  write_dcg_diff_edges(writer, "dcg-diff-edges-removed",    "edges removed",    &diff.edges_removed);
+  write_dcg_diff_edges(writer, "dcg-diff-edges-dirtied",    "edges dirtied",    &diff.edges_dirtied);
+  write_dcg_diff_edges(writer, "dcg-diff-edges-cleaned",    "edges cleaned",    &diff.edges_cleaned);
+  writeln!(writer, "</div>").unwrap();
+}
+
 pub fn write_cr<W:Write>(writer:&mut W) {
   /// We style this with clear:both, and without any appearance
   writeln!(writer, "<hr/>").unwrap();
@@ -516,6 +829,42 @@ pub fn write_lab_name<W:Write>(writer:&mut W, lab:&Box<Lab>, is_title:bool) {
   ).unwrap();
 }
 
+/// Escapes text for HTML (and SVG), in content or in a quoted
+/// attribute value.
+pub fn escape_html(s:&str) -> String {
+  s.replace("&", "&amp;").replace("<", "&lt;").replace(">", "&gt;").replace("\"", "&quot;")
+}
+
+/// Writes the facts about a lab (See `LabInfo`): its description, its
+/// tags, whether it is lazy, its types, its defaults and its presets.
+pub fn write_lab_info<W:Write>(writer:&mut W, lab:&Box<Lab>) {
+  let info  = lab.info();
+  let types = lab.types();
+  writeln!(writer, "<div class={:?}>", "lab-info").unwrap();
+  if info.description.len() > 0 {
+    writeln!(writer, "<div class={:?}>{}</div>", "lab-description", escape_html(info.description)).unwrap();
+  };
+  for tag in info.tags.iter() {
+    writeln!(writer, "<span class={:?}>{}</span>", "lab-tag", escape_html(tag)).unwrap();
+  };
+  if info.lazy {
+    writeln!(writer, "<span class={:?}>lazy: uses the demand</span>", "lab-tag").unwrap();
+  };
+  writeln!(writer, "<div class={:?}>Input <code>{}</code>, Output <code>{}</code>, Editor <code>{}</code> (state <code>{}</code>), Archivist <code>{}</code></div>",
+           "lab-types", escape_html(types.input), escape_html(types.output), escape_html(types.editor),
+           escape_html(types.editst), escape_html(types.archivist)).unwrap();
+  if !info.defaults.is_empty() {
+    writeln!(writer, "<div class={:?}>Defaults: {}</div>", "lab-defaults", info.defaults).unwrap();
+  };
+  if !info.viz.is_empty() {
+    writeln!(writer, "<div class={:?}>Viz preset: {}</div>", "lab-defaults", info.viz).unwrap();
+  };
+  if !info.bench.is_empty() {
+    writeln!(writer, "<div class={:?}>Bench preset: {}</div>", "lab-defaults", info.bench).unwrap();
+  };
+  writeln!(writer, "</div>").unwrap();
+}
+
 pub fn write_dcg_tree<W:Write> (writer:&mut W, dcg:&DCG, traces:&Vec<trace::Trace>) {
   let mut visited = HashMap::new();
   let mut extent : Vec<_> = Vec::new();
@@ -682,6 +1031,27 @@ pub fn write_sample_dcg<W:Write>
     }
   };
 
+  // Diffs: What the edit touched (from the previous update), and
+  // what change propagation touched (from this edit).
+  match this_sample.dcg_sample.process_input.reflect_dcg {
+    Some(ref dcg_post_edit) => {
+      match prev_sample {
+        Some(ref prev_sample) => match prev_sample.dcg_sample.compute_output.reflect_dcg {
+          Some(ref dcg_prev_update) =>
+            write_dcg_diff(writer, "DCG diff, edit:", &diff_dcg(dcg_prev_update, dcg_post_edit)),
+          None => writeln!(writer, "<div class=\"dcg-diff\"></div>").unwrap(),
+        },
+        None => writeln!(writer, "<div class=\"dcg-diff\"></div>").unwrap(),
+      };
+      match this_sample.dcg_sample.compute_output.reflect_dcg {
+        Some(ref dcg_post_update) =>
+          write_dcg_diff(writer, "DCG diff, update:", &diff_dcg(dcg_post_edit, dcg_post_update)),
+        None => (),
+      };
+      write_cr(writer);
+    },
+    None => (),
+  }
 }
 
 pub fn write_lab_results(params:&LabParams, lab:&Box<Lab>, results:&LabResults) {
@@ -698,108 +1068,143 @@ pub fn write_lab_results(params:&LabParams, lab:&Box<Lab>, results:&LabResults)
   //let trace_url   = "http://adapton.org/rustdoc/adapton/engine/reflect/trace/struct.Trace.html";
   
   // Create directories and files on local filesystem:
-  fs::create_dir_all(format!("lab-results/{}/", labname)).unwrap();
-  let f = File::create(format!("lab-results/{}/index.html", labname)).unwrap();
+  fs::create_dir_all(format!("{}/{}/", params.output_dir, labname)).unwrap();
+  let f = File::create(format!("{}/{}/index.html", params.output_dir, labname)).unwrap();
   let mut writer = BufWriter::new(f);
   writeln!(writer, "{}", style_string()).unwrap();  
   writeln!(writer, "<a href=\"../index.html\">↰ Results summary</a>").unwrap();
   write_cr(&mut writer);
   write_lab_name(&mut writer, lab, true);
+  write_lab_info(&mut writer, lab);
   writeln!(writer, "<div style=\"font-size:12px\" class=\"batch-name\"> step</div>").unwrap();  
   if write_times {
     writeln!(writer, "<div style=\"font-size:20px\" class=\"editor\">Editor</div>").unwrap();
     writeln!(writer, "<div style=\"font-size:20px\" class=\"archivist\">Archivist</div>").unwrap();
   }  
-  let mut prev_sample = None;
-  for sample in results.samples.iter() {
-    write_cr(&mut writer);
-    // - - - - - - - 
-    // 0. Write batch name (a counter); and write timing information for this edit batch.
-    writeln!(writer, "<div class=\"batch-name-lab\">batch name<div class=\"batch-name\">{:?}</div></div>", 
-             sample.batch_name).unwrap();
+  for trajectory in results.trajectories.iter() {
+    if results.trajectories.len() > 1 {
+      write_cr(&mut writer);
+      writeln!(writer, "<div class=\"seed-name-lab\">input seed<div class=\"seed-name\">{:?}</div></div>", 
+               trajectory.seed).unwrap();
+    }
+    let mut prev_sample = None;
+    for sample in trajectory.samples.iter() {
+      write_cr(&mut writer);
+      // - - - - - - - 
+      // 0. Write batch name (a counter); and write timing information for this edit batch.
+      writeln!(writer, "<div class=\"batch-name-lab\">batch name<div class=\"batch-name\">{:?}</div></div>", 
+               sample.batch_name).unwrap();
     
-    if write_times {
-      writeln!(writer, "<div class=\"editor\">").unwrap();
+      if write_times {
+        writeln!(writer, "<div class=\"editor\">").unwrap();
       
-      writeln!(writer, "<div class=\"time-ns-lab\">time (ns): <div class=\"time-ns\">{:?}</div></div>", 
-               sample.dcg_sample.process_input.time_ns).unwrap();    
-      writeln!(writer, "</div>").unwrap();
+        writeln!(writer, "<div class=\"time-ns-lab\">median time (ns): <div class=\"time-ns\">{:?}</div></div>", 
+                 sample.dcg_sample.process_input.time_stats.median_ns).unwrap();
+        write_timing_stats(&mut writer, &sample.dcg_sample.process_input.time_stats);
+        writeln!(writer, "<div class=\"batch-size-lab\">edits: <div class=\"batch-size\">{:?}</div></div>",
+                 sample.dcg_sample.process_input.batch_times_ns.len()).unwrap();
+        writeln!(writer, "</div>").unwrap();
       
-      writeln!(writer, "<div class=\"archivist\">").unwrap();
+        writeln!(writer, "<div class=\"archivist\">").unwrap();
       
-      writeln!(writer, "<div class=\"row\">").unwrap();
+        writeln!(writer, "<div class=\"row\">").unwrap();
       
-      writeln!(writer, "<div class=\"time-ns-lab\">Naive time (ns): <div class=\"time-ns\">{:?}</div></div>", 
-               sample.naive_sample.compute_output.time_ns).unwrap();    
+        let naive_stats = &sample.naive_sample.compute_output.time_stats;
+        let dcg_stats   = &sample.dcg_sample.compute_output.time_stats;
+
+        writeln!(writer, "<div class=\"time-ns-lab\">Naive median time (ns): <div class=\"time-ns\">{:?}</div></div>", 
+                 naive_stats.median_ns).unwrap();    
       
-      writeln!(writer, "<div class=\"time-ms-lab\">Naive time (ms): <div class=\"time-ms\">{:.*}</div></div>", 
-               2, (sample.naive_sample.compute_output.time_ns as f64) / (1000_000 as f64)).unwrap();
-      writeln!(writer, "</div>").unwrap();
+        writeln!(writer, "<div class=\"time-ms-lab\">Naive median time (ms): <div class=\"time-ms\">{:.*}</div></div>", 
+                 2, (naive_stats.median_ns as f64) / (1000_000 as f64)).unwrap();
+        write_timing_stats(&mut writer, naive_stats);
+        writeln!(writer, "</div>").unwrap();
       
-      writeln!(writer, "<div class=\"row\">").unwrap();
-      writeln!(writer, "<div class=\"time-ns-lab\">DCG time (ns): <div class=\"time-ns\">{:?}</div></div>", 
-               sample.dcg_sample.compute_output.time_ns).unwrap();    
+        writeln!(writer, "<div class=\"row\">").unwrap();
+        writeln!(writer, "<div class=\"time-ns-lab\">DCG median time (ns): <div class=\"time-ns\">{:?}</div></div>", 
+                 dcg_stats.median_ns).unwrap();    
       
-      writeln!(writer, "<div class=\"time-ms-lab\">DCG time (ms): <div class=\"time-ms\">{:.*}</div></div>", 
-               2, (sample.dcg_sample.compute_output.time_ns as f64) / (1000_000 as f64)).unwrap();
-      writeln!(writer, "</div>").unwrap();
+        writeln!(writer, "<div class=\"time-ms-lab\">DCG median time (ms): <div class=\"time-ms\">{:.*}</div></div>", 
+                 2, (dcg_stats.median_ns as f64) / (1000_000 as f64)).unwrap();
+        write_timing_stats(&mut writer, dcg_stats);
+        writeln!(writer, "</div>").unwrap();
       
-      if sample.naive_sample.compute_output.time_ns <
-        sample.dcg_sample.compute_output.time_ns {
-          writeln!(writer, "<div class=\"overhead-lab\">DCG Overhead: <div class=\"overhead\">{:.*}</div></div>", 
-                   2, ( (sample.dcg_sample.compute_output.time_ns  as f64) / 
-                         (sample.naive_sample.compute_output.time_ns as f64) )).unwrap();      
-        } else {      
-          writeln!(writer, "<div class=\"speedup-lab\">DCG Speedup: <div class=\"speedup\">{:.*}</div></div>", 
-                   2, ( (sample.naive_sample.compute_output.time_ns  as f64) / 
-                         (sample.dcg_sample.compute_output.time_ns as f64) )).unwrap();
-        }    
+        if naive_stats.median_ns < dcg_stats.median_ns {
+            writeln!(writer, "<div class=\"overhead-lab\">DCG Overhead: <div class=\"overhead\">{:.*}</div></div>", 
+                     2, ( (dcg_stats.median_ns   as f64) / 
+                           (naive_stats.median_ns as f64) )).unwrap();      
+          } else {      
+            writeln!(writer, "<div class=\"speedup-lab\">DCG Speedup: <div class=\"speedup\">{:.*}</div></div>", 
+                     2, ( (naive_stats.median_ns as f64) / 
+                           (dcg_stats.median_ns   as f64) )).unwrap();
+          }    
       
-      writeln!(writer, "</div>").unwrap();
-      write_cr(&mut writer);    
-    }
+        writeln!(writer, "</div>").unwrap();
+        write_cr(&mut writer);    
+      }
 
-    // 1. Write input,
-    // 2. Write output,
-    // 3. Write last DCG, after edit but before update.
-    // 4. Write DCG of the update.
-    write_sample_dcg(&mut writer, lab, prev_sample, sample);      
+      if params.sample_params.count_effects {
+        write_effect_counts(&mut writer, "Editor effects:", &sample.dcg_sample.process_input.effect_counts);
+        write_effect_counts(&mut writer, "Archivist effects:", &sample.dcg_sample.compute_output.effect_counts);
+        write_cr(&mut writer);
+      }
+
+      // 1. Write input,
+      // 2. Write output,
+      // 3. Write last DCG, after edit but before update.
+      // 4. Write DCG of the update.
+      write_sample_dcg(&mut writer, lab, prev_sample, sample);      
     
-    if sample.dcg_sample.compute_output.reflect_traces.len() == 0 {
-      // 5 & 6. No traces to write.
-    } else {
-      // - - - - - - - 
-      // 5. Write traces of editor
+      if sample.dcg_sample.compute_output.reflect_traces.len() == 0 {
+        // 5 & 6. No traces to write.
+      } else {
+        // - - - - - - - 
+        // 5. Write traces of editor
       
-      writeln!(writer, "<div class=\"traces-box\">").unwrap();
-      // writeln!(writer, "<div class=\"time-ns-lab\">time (ns): <div class=\"time-ns\">{:?}</div></div>", 
-      //          sample.dcg_sample.process_input.time_ns).unwrap();    
-      // writeln!(writer, "<div class=\"traces-lab\">Traces (<a href={:?}>doc</a>)</div>", trace_url).unwrap();    
-      writeln!(writer, "<div class=\"label\">{}</div>", "Editor trace:").unwrap();
-      writeln!(writer, "<div class=\"traces\">").unwrap();
-      for tr in sample.dcg_sample.process_input.reflect_traces.iter() {
-        div_of_trace(tr).write_html(&mut writer)
-      }
-      writeln!(writer, "</div>").unwrap();   
-      writeln!(writer, "</div>").unwrap();
+        writeln!(writer, "<div class=\"traces-box\">").unwrap();
+        // writeln!(writer, "<div class=\"time-ns-lab\">time (ns): <div class=\"time-ns\">{:?}</div></div>", 
+        //          sample.dcg_sample.process_input.time_ns).unwrap();    
+        // writeln!(writer, "<div class=\"traces-lab\">Traces (<a href={:?}>doc</a>)</div>", trace_url).unwrap();    
+        writeln!(writer, "<div class=\"label\">{}</div>", "Editor trace:").unwrap();
+        writeln!(writer, "<div class=\"traces\">").unwrap();
+        for tr in sample.dcg_sample.process_input.reflect_traces.iter() {
+          div_of_trace(tr).write_html(&mut writer)
+        }
+        writeln!(writer, "</div>").unwrap();   
+        writeln!(writer, "</div>").unwrap();
       
-      // - - - - - - - 
-      // 6. Write traces of archivist
-
-      //writeln!(writer, "<div class=\"traces-lab\">Traces (<a href={:?}>doc</a>):</div>", trace_url).unwrap();
-      writeln!(writer, "<div class=\"traces-box\">").unwrap();
-      writeln!(writer, "<div class=\"label\">{}</div>", "Archivist trace:").unwrap();
-      writeln!(writer, "<div class=\"traces\">").unwrap();
-      for tr in sample.dcg_sample.compute_output.reflect_traces.iter() {
-        div_of_trace(tr).write_html(&mut writer)
+        // - - - - - - - 
+        // 6. Write traces of archivist
+
+        //writeln!(writer, "<div class=\"traces-lab\">Traces (<a href={:?}>doc</a>):</div>", trace_url).unwrap();
+        writeln!(writer, "<div class=\"traces-box\">").unwrap();
+        writeln!(writer, "<div class=\"label\">{}</div>", "Archivist trace:").unwrap();
+        writeln!(writer, "<div class=\"traces\">").unwrap();
+        for tr in sample.dcg_sample.compute_output.reflect_traces.iter() {
+          div_of_trace(tr).write_html(&mut writer)
+        }
+        writeln!(writer, "</div>").unwrap();    
+        writeln!(writer, "</div>").unwrap();
+        write_cr(&mut writer);
+      }    
+    
+      // - - - - - - - - - - - - - - -       
+      prev_sample = Some(sample) ; // Must be last!
+    }
+    match trajectory.failure {
+      None => (),
+      Some(ref failure) => {
+        write_cr(&mut writer);
+        write_lab_failure(&mut writer, failure);
       }
-      writeln!(writer, "</div>").unwrap();    
-      writeln!(writer, "</div>").unwrap();
+    }
+  }
+  match results.failure {
+    None => (),
+    Some(ref failure) => {
       write_cr(&mut writer);
-    }    
-    
-    // - - - - - - - - - - - - - - -       
-    prev_sample = Some(sample) ; // Must be last!
+      write_lab_failure(&mut writer, failure);
+    }
   }
   writer.flush().unwrap();  
 }
@@ -808,7 +1213,7 @@ pub fn style_string() -> &'static str {
 "
 <html>
 <head>
-<script src=\"https://ajax.googleapis.com/ajax/libs/jquery/3.1.1/jquery.min.js\"></script>
+<meta charset=\"utf-8\">
 
 <style>
 div { 
@@ -880,6 +1285,16 @@ hr {
   margin: 3px;
 }
 
+.seed-name-lab {
+  display: block;
+  font-size: 16px;
+  margin: 8px;
+}
+.seed-name {
+  display: inline;
+  font-size: 20px;
+  padding: 3px;
+}
 .batch-name-lab {
   font-size: 0px;
   color: black;
@@ -903,6 +1318,111 @@ hr {
   font-size: 20px;
   display: inline;
 }
+.effect-counts {
+  display: inline;
+  float: left;
+  width: 49%;
+  margin: 2px;
+  font-size: 10px;
+}
+.effect-count-lab {
+  display: inline-block;
+  margin-right: 8px;
+}
+.effect-count {
+  display: inline;
+  font-weight: bold;
+}
+.lab-failure {
+  display: block;
+  font-size: 14px;
+  color: white;
+  background: #aa2222;
+  border-radius: 5px;
+  padding: 4px;
+  margin: 2px;
+}
+.lab-failure-msg {
+  display: inline;
+  font-family: monospace;
+}
+.labsum-table {
+  display: table;
+  margin: 8px;
+  border-collapse: collapse;
+  font-size: 13px;
+}
+.labsum-table th {
+  cursor: pointer;
+  color: white;
+  background: #441155;
+  padding: 4px;
+}
+.labsum-table td {
+  padding: 2px 6px;
+  text-align: right;
+  border-bottom: solid #441155 1px;
+}
+.labsum-table-bad td {
+  color: #ffcccc;
+}
+.labsum-charts {
+  display: block;
+}
+.lab-info {
+  display: block;
+  font-size: 12px;
+  margin: 2px;
+}
+.lab-description {
+  display: block;
+  margin-bottom: 2px;
+}
+.lab-tag {
+  display: inline;
+  border: solid #ccaadd 1px;
+  border-radius: 5px;
+  padding: 1px 3px;
+  margin-right: 2px;
+}
+.lab-types, .lab-defaults {
+  display: block;
+  margin-top: 2px;
+  color: #ccaadd;
+}
+.chart {
+  background: white;
+  border-radius: 4px;
+  margin: 4px;
+}
+.labsum-failures {
+  display: block;
+  padding: 4px;
+  margin: 4px;
+}
+.labsum-failed-lab {
+  display: block;
+  font-size: 16px;
+}
+.labsum-failed {
+  display: inline;
+  color: white;
+  background: #aa2222;
+  border-radius: 5px;
+  padding: 2px;
+  margin: 4px;
+}
+.time-stats {
+  display: block;
+  font-size: 10px;
+}
+.batch-size-lab {
+  display: block;
+  font-size: 12px;
+}
+.batch-size {
+  display: inline;
+}
 .overhead {
   font-size: 30px;
   display: inline;
@@ -958,6 +1478,7 @@ hr {
 
 .input-value, 
 .output-value,
+.dcg-diff,
 .archivist-dcg-tree-post-edit,
 .archivist-dcg-tree-post-update,
 .traces-box,
@@ -981,9 +1502,34 @@ hr {
 }
 
 .input-value, 
-.output-value {
+.output-value,
+.dcg-diff {
   width: 49%;
 }
+.dcg-diff-part {
+  display: block;
+  margin: 4px;
+}
+.dcg-diff-lab {
+  display: block;
+  font-size: 12px;
+}
+.dcg-diff-loc {
+  display: block;
+  font-size: 10px;
+  font-family: monospace;
+  color: white;
+}
+.dcg-diff-nodes-added .dcg-diff-loc,
+.dcg-diff-edges-added .dcg-diff-loc,
+.dcg-diff-edges-cleaned .dcg-diff-loc {
+  color: #88ff88;
+}
+.dcg-diff-nodes-removed .dcg-diff-loc,
+.dcg-diff-edges-removed .dcg-diff-loc,
+.dcg-diff-edges-dirtied .dcg-diff-loc {
+  color: #ff8888;
+}
 .traces-box {
   width: 99%;
 }
@@ -1221,39 +1767,66 @@ hr {
 </style>
 
 <script>
+// Self-contained: no external scripts, so that the pages work offline.
+function setDisplay(className, display) {
+ var elms = document.getElementsByClassName(className);
+ for (var i = 0; i < elms.length; i++) {
+   elms[i].style.display = display;
+ }
+}
+
+// Sorts the rows of a table by a column, using the attribute
+// `data-sort` of each cell: numerically if both are numbers, and
+// otherwise as text; empty values go last.  Clicking the same column
+// again reverses the order.
+function sortTable(tableId, col) {
+ var table = document.getElementById(tableId);
+ var rows = Array.prototype.slice.call(table.rows, 1);
+ var desc = table.getAttribute('data-sort-col') == String(col) && table.getAttribute('data-sort-dir') != 'desc';
+ rows.sort(function(a, b) {
+   var x = a.cells[col].getAttribute('data-sort'), y = b.cells[col].getAttribute('data-sort');
+   if (x === '' || y === '') { return (x === '') - (y === '') }
+   var c = (isNaN(x) || isNaN(y)) ? x.localeCompare(y) : Number(x) - Number(y);
+   return desc ? -c : c;
+ });
+ for (var i = 0; i < rows.length; i++) { rows[i].parentNode.appendChild(rows[i]) }
+ table.setAttribute('data-sort-col', String(col));
+ table.setAttribute('data-sort-dir', desc ? 'desc' : 'asc');
+}
+
 function togglePaths() {
  var selection = document.getElementById(\"checkbox-1\");
  if (selection.checked) {
-   $('.path').css('display', 'inline-block')
+   setDisplay('path', 'inline-block')
  } else {
-   $('.path').css('display', 'none')
+   setDisplay('path', 'none')
  }
 }
 
 function toggleNames() {
  var selection = document.getElementById(\"checkbox-2\");
  if (selection.checked) {
-   $('.name').css('display', 'inline')
+   setDisplay('name', 'inline')
  } else {
-   $('.name').css('display', 'none')
+   setDisplay('name', 'none')
  }
 }
 
 function toggleEffects() {
  var selection = document.getElementById(\"checkbox-3\");
  if (selection.checked) {
-   $('.tr-effect').css('display', 'inline')
+   setDisplay('tr-effect', 'inline')
  } else {
-   $('.tr-effect').css('display', 'none')
+   setDisplay('tr-effect', 'none')
  }
 }
 
 function toggleDupForces() {
  var selection = document.getElementById(\"checkbox-4\");
  if (selection.checked) {
-   $('.tr-force-dup').css('display', 'inline')
+   setDisplay('tr-force-dup', 'inline')
  } else {
-   $('.tr-force-dup').css('display', 'none')
+   setDisplay('tr-force-dup', 'none')
  }
 }
 </script>
@@ -8,8 +8,9 @@
 //extern crate serialize;
 //extern crate csv;
 extern crate rand;
+extern crate regex;
+extern crate toml;
 
-//#[macro_use]
 extern crate clap;
 
 //#[macro_use]
@@ -31,3 +32,52 @@ pub mod labviz;
 /// **Generically implements** the test diagram in the [Adapton Lab
 /// README](https://github.com/cuplv/adapton-lab.rust).
 pub mod labrun;
+
+/// Writes lab results in **machine-readable formats** (CSV, TSV and
+/// JSON), with one row per sample, e.g., for GNUPLOT or a spreadsheet.
+pub mod labdata;
+
+/// Computes **structural diffs** of reflected DCGs, e.g., to see
+/// what an edit and its change propagation touched.
+pub mod labdiff;
+
+/// Writes reflected DCGs as **Graphviz DOT** graphs, one per sample
+/// and phase, for rendering and diffing with standard graph tools.
+pub mod labdot;
+
+/// Runs each lab in a **child process**, with a timeout, so that
+/// stack overflows and non-terminating labs are reported as failures.
+pub mod labproc;
+
+/// Records and replays **edit scripts**: the edits of a trajectory,
+/// as text, for replaying it exactly (e.g., from a checked-in file).
+pub mod labscript;
+
+/// Runs labs over a **sweep** of parameter points (e.g., input sizes),
+/// writing one table with a row per lab and point, e.g., for plots.
+pub mod labsweep;
+
+/// Draws **charts** as inline SVG (e.g., compute times per batch, and
+/// speedups versus input size), with no external libraries.
+pub mod labplot;
+
+/// Compares a run to a **baseline** (e.g., an exported `samples.csv`),
+/// reporting regressions in DCG update times and effect counts.
+pub mod labcompare;
+
+/// The **command-line driver** of Adapton Lab, and a runner API (run
+/// one lab, run a set, write reports), for any catalog of labs, e.g.,
+/// the labs of another crate (See `labdef!`).
+pub mod labmain;
+
+/// Checks the **from-scratch consistency** of a lab, e.g., from a
+/// cargo test generated by `lab_test!`.
+pub mod labtest;
+
+/// **Selects labs** from a catalog by name (exactly, or with globs or
+/// regexes) and by tag, e.g., all lazy labs but the mergesorts.
+pub mod labselect;
+/// Describes experiments in a **configuration file** (TOML), e.g., a
+/// versioned benchmark setup, and writes the effective configuration
+/// of each run next to its results.
+pub mod labconfig;